@@ -1,48 +1,35 @@
 use async_trait::async_trait;
 use env_logger::init;
-use fungraph::{
-    llm::Message,
-    node::{FunGraph, FunGraphBuilder, FunNode, FunState},
-    *,
+use fungraph::node::{
+    CompileError, FunGraph, FunGraphBuilder, FunNode, FunState, GraphError, MemorySaver, NodeError,
+    RunConfig,
 };
-use log::{debug, info};
-use petgraph::{Graph, data::DataMap, graph::NodeIndex, visit::IntoNeighbors};
-use std::io;
+use log::debug;
+use std::{io, sync::Arc};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, FunState)]
 struct ChatbotState {
     pub message: Option<String>,
+    #[reducer(append)]
     pub histories: Vec<String>,
 }
 
-impl FunState for ChatbotState {}
-
 #[derive(Debug)]
 struct InputNode {}
 
-/// ユーザーからの入力を受け取るステートノード
+/// ユーザーからの入力を履歴に追加するステートノード
+/// 入力は実行前の中断 (interrupt_before) で受け取る
 #[async_trait]
 impl FunNode<ChatbotState> for InputNode {
     fn get_name(&self) -> String {
         "InputNode".to_string()
     }
 
-    async fn run(&self, state: ChatbotState) -> ChatbotState {
-        // 標準入力からユーザーの入力を受け取る
-        println!("何か入力してください:");
-        let mut input = String::new();
-
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
-
-        let input = input.trim();
+    async fn run(&self, state: ChatbotState) -> Result<ChatbotStateUpdate, NodeError> {
+        let input = state.message.unwrap_or_default();
         println!("入力された内容: {}", input);
 
-        ChatbotState {
-            message: Some(input.to_string()),
-            histories: state.histories,
-        }
+        Ok(ChatbotStateUpdate::new().with_histories(vec![input]))
     }
 }
 
@@ -56,12 +43,9 @@ impl FunNode<ChatbotState> for OutputNode {
         "OutputNode".to_string()
     }
 
-    async fn run(&self, state: ChatbotState) -> ChatbotState {
-        println!("出力: {}", state.message.clone().unwrap());
-        ChatbotState {
-            message: state.message,
-            histories: state.histories,
-        }
+    async fn run(&self, state: ChatbotState) -> Result<ChatbotStateUpdate, NodeError> {
+        println!("出力: {}", state.message.unwrap_or_default());
+        Ok(ChatbotStateUpdate::new())
     }
 }
 
@@ -70,14 +54,17 @@ struct ChatBotAgent {
 }
 
 impl ChatBotAgent {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, CompileError> {
         let input_node = InputNode {};
         let output_node = OutputNode {};
 
-        let mut graph: FunGraph<ChatbotState> = FunGraph::new();
-        let a = graph.add_node(input_node);
-        let b = graph.add_node(output_node);
-        graph.add_edge(a, b, "Edge AB".to_string());
+        let mut builder: FunGraphBuilder<ChatbotState> = FunGraphBuilder::new();
+        let a = builder.add_node(input_node);
+        let b = builder.add_node(output_node);
+        builder.add_edge(a, b, "Edge AB".to_string());
+        builder.add_edge(b, builder.end(), "Edge B END".to_string());
+        // 入力を受け取るまで実行を中断する
+        builder.interrupt_before(a);
         // 制約
         // conditionalじゃない場合はadd_edgeで同一fromで複数toを追加できないようにしたい。
         // conditionalの場合は複数toが設定できる
@@ -87,26 +74,48 @@ impl ChatBotAgent {
         //let b = graph.add_node(Box::new(output_node));
         //graph.add_edge(a, b, "Edge AB".to_string());
 
-        ChatBotAgent { graph }
+        let graph = builder
+            .compile()?
+            .with_checkpointer(Arc::new(MemorySaver::new()));
+        Ok(ChatBotAgent { graph })
     }
 
-    pub async fn run(&self) {
+    pub async fn run(&self) -> Result<ChatbotState, GraphError<ChatbotState>> {
         let initial_state = ChatbotState {
             message: None,
             histories: vec![],
         };
-        self.graph.run(initial_state).await;
+        let config = RunConfig::default().with_thread_id("chatbot");
+        match self.graph.run_with_config(initial_state, &config).await {
+            Err(GraphError::Interrupted { mut state, handle }) => {
+                // 標準入力からユーザーの入力を受け取り、状態を更新して再開する
+                state.message = Some(read_input().map_err(|e| GraphError::NodeError {
+                    node: "InputNode".to_string(),
+                    state: state.clone(),
+                    source: NodeError::OtherError(format!("Failed to read line: {}", e)),
+                })?);
+                self.graph.resume(&handle, state).await
+            }
+            result => result,
+        }
     }
 }
 
+fn read_input() -> io::Result<String> {
+    println!("何か入力してください:");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv()?;
     init();
     debug!("Starting chatbot example");
 
-    let agent = ChatBotAgent::new();
-    agent.run().await;
+    let agent = ChatBotAgent::new()?;
+    agent.run().await?;
 
     Ok(())
 }
@@ -3,7 +3,7 @@ use fungraph::llm::{
     LLM, LLMResult, Messages,
     gemini::{Gemini, GeminiConfigBuilder},
 };
-use log::{debug, info};
+use log::debug;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -23,9 +23,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         LLMResult::ToolCall(tool_call) => {
             debug!("Received tool call: {:?}", tool_call);
         }
-        _ => {
-            info!("Error");
-        }
     }
     Ok(())
 }
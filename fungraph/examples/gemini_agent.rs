@@ -13,6 +13,7 @@ use serde_json::Value;
 
 struct WeatherTool;
 
+#[allow(dead_code)]
 #[derive(ToolParameters)]
 struct WeatherToolParameters {
     /// 天気を取得したい場所を指定します。例. "東京"
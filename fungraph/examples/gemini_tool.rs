@@ -10,12 +10,12 @@ use fungraph::{
     },
     tools::Tool,
 };
-use log::{debug, info};
+use log::debug;
 use serde_json::Value;
-use tokio_stream::StreamExt;
 
 struct WeatherTool;
 
+#[allow(dead_code)]
 #[derive(ToolParameters)]
 struct WeatherToolParameters {
     /// 天気を取得したい場所を指定します。例. "東京"
@@ -55,7 +55,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .add_human_message("今日の東京の天気は？")
         .add_tools(vec![tool.to_openai_tool()])
         .build();
-    let mut response = gemini.invoke(&messages).await?;
+    let response = gemini.invoke(&messages).await?;
 
     match response {
         LLMResult::Generate(result) => {
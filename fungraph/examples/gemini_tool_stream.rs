@@ -16,6 +16,7 @@ use tokio_stream::StreamExt;
 
 struct WeatherTool;
 
+#[allow(dead_code)]
 #[derive(ToolParameters)]
 struct WeatherToolParameters {
     /// 天気を取得したい場所を指定します。例. "東京"
@@ -1,10 +1,10 @@
-use std::{any::Any, collections::HashMap};
+use std::collections::HashMap;
 
 use async_trait::async_trait;
 use log::{debug, info};
 
 use crate::{
-    llm::{self, GenerateResult, LLM, LLMError, LLMResult, Message, Messages, MessagesBuilder},
+    llm::{LLM, LLMError, LLMResult, Message, Messages, MessagesBuilder},
     tools::Tool,
 };
 
@@ -57,8 +57,8 @@ where
 
         let tools = self
             .tools
-            .iter()
-            .map(|(_, tool)| tool.to_openai_tool())
+            .values()
+            .map(|tool| tool.to_openai_tool())
             .collect::<Vec<_>>();
 
         if !tools.is_empty() {
@@ -94,7 +94,7 @@ where
                     let result = tool.call(&tool_call_result.arguments).await;
                     debug!("LLMAgent: Tool call result: {:?}", result);
                     let tool_message =
-                        Message::new_tool_message(result?, &tool_call_result.id.to_string());
+                        Message::new_tool_message(result?, tool_call_result.id.to_string());
                     messages.add_message(tool_message);
 
                     // TODO: ここで実際にinvokeする
@@ -132,7 +132,7 @@ where
 {
     pub fn new(llm: T) -> Self {
         LLMAgentBuilder {
-            llm: llm,
+            llm,
             system_prompt: None,
             tools: HashMap::new(),
         }
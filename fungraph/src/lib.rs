@@ -1,5 +1,12 @@
+// LLMError keeps reqwest_eventsource::Error as is, which makes every Result<_, LLMError> large.
+#![allow(clippy::result_large_err)]
+
 pub mod agent;
 pub mod llm;
 pub mod node;
 pub mod tools;
 pub mod types;
+
+// Used by the code generated by `#[derive(FunState)]`.
+#[doc(hidden)]
+pub use serde;
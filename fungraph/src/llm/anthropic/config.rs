@@ -0,0 +1,134 @@
+use std::fmt;
+
+use anyhow::Result;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnthropicModel {
+    ClaudeOpus4,
+    ClaudeSonnet4,
+    Claude35Haiku,
+    /// Any other model name or a pinned snapshot, e.g. `claude-sonnet-4-20250514`.
+    Other(String),
+}
+
+impl fmt::Display for AnthropicModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnthropicModel::ClaudeOpus4 => write!(f, "claude-opus-4-0"),
+            AnthropicModel::ClaudeSonnet4 => write!(f, "claude-sonnet-4-0"),
+            AnthropicModel::Claude35Haiku => write!(f, "claude-3-5-haiku-latest"),
+            AnthropicModel::Other(model) => write!(f, "{}", model),
+        }
+    }
+}
+
+impl From<AnthropicModel> for String {
+    fn from(val: AnthropicModel) -> Self {
+        val.to_string()
+    }
+}
+
+#[derive(Clone)]
+pub struct AnthropicConfig {
+    api_base: String,
+    api_key: String,
+    model: AnthropicModel,
+    max_tokens: u32,
+}
+
+impl Default for AnthropicConfig {
+    fn default() -> Self {
+        Self {
+            api_base: "https://api.anthropic.com/v1".to_string(),
+            api_key: "".to_string(),
+            model: AnthropicModel::ClaudeSonnet4,
+            max_tokens: 4096,
+        }
+    }
+}
+
+impl AnthropicConfig {
+    pub fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+    pub fn model(&self) -> &AnthropicModel {
+        &self.model
+    }
+    /// Upper limit of generated tokens. The Messages API requires it on every request.
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens
+    }
+}
+
+pub struct AnthropicConfigBuilder {
+    config: AnthropicConfig,
+}
+
+impl Default for AnthropicConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnthropicConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: AnthropicConfig::default(),
+        }
+    }
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.config.api_base = api_base.into();
+        self
+    }
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.config.api_key = api_key.into();
+        self
+    }
+    pub fn with_model(mut self, model: AnthropicModel) -> Self {
+        self.config.model = model;
+        self
+    }
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.config.max_tokens = max_tokens;
+        self
+    }
+    pub fn build(self) -> Result<AnthropicConfig> {
+        if self.config.api_key.is_empty() {
+            anyhow::bail!("API key must be set");
+        }
+
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anthropic_config_builder_api_key_empty() {
+        let result = AnthropicConfigBuilder::new().build();
+        match result {
+            Ok(_) => panic!("API key must be required"),
+            Err(err) => assert_eq!(err.to_string(), "API key must be set"),
+        }
+    }
+
+    #[test]
+    fn test_anthropic_config_builder_all_fields() {
+        let config = AnthropicConfigBuilder::new()
+            .with_api_base("https://example.com")
+            .with_api_key("test_api_key")
+            .with_model(AnthropicModel::Claude35Haiku)
+            .with_max_tokens(1024)
+            .build()
+            .unwrap();
+        assert_eq!(config.api_base, "https://example.com");
+        assert_eq!(config.api_key, "test_api_key");
+        assert_eq!(config.model, AnthropicModel::Claude35Haiku);
+        assert_eq!(config.max_tokens, 1024);
+    }
+}
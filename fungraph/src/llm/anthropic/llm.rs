@@ -0,0 +1,698 @@
+use std::{collections::BTreeMap, pin::Pin};
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt, stream};
+use log::debug;
+use reqwest::header::CONTENT_TYPE;
+use reqwest_eventsource::RequestBuilderExt;
+use serde_json::{Value, json};
+
+use crate::{
+    llm::{
+        CallOptions, ChatStream, GenerateResult, LLM, LLMError, LLMResult, Message, MessageType,
+        Messages, ToolCallResult, emit_token, sse::stream,
+    },
+    types::{TokenUsage, openai::ChatCompletionMessageToolCall},
+};
+
+use super::{
+    AnthropicConfig, AnthropicMessage, AnthropicRequest, AnthropicResponse, AnthropicTool,
+    AnthropicUsage, ContentBlock, ContentDelta, StreamEvent,
+};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Clone)]
+pub struct Anthropic {
+    config: AnthropicConfig,
+    options: CallOptions,
+}
+
+impl Anthropic {
+    pub fn new(config: AnthropicConfig) -> Self {
+        Self {
+            config,
+            options: CallOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn build_request(
+        &self,
+        messages: &Messages,
+        is_stream: bool,
+    ) -> Result<AnthropicRequest, LLMError> {
+        let (system, anthropic_messages) = to_anthropic_messages(messages)?;
+        let tools: Vec<AnthropicTool> = messages
+            .tools
+            .iter()
+            .map(|tool| AnthropicTool {
+                name: tool.function.name.clone(),
+                description: tool.function.description.clone(),
+                input_schema: tool.function.parameters.clone(),
+            })
+            .collect();
+        Ok(AnthropicRequest {
+            model: self.config.model().clone().into(),
+            max_tokens: self
+                .options
+                .max_tokens
+                .unwrap_or_else(|| self.config.max_tokens()),
+            system,
+            messages: anthropic_messages,
+            tools: (!tools.is_empty()).then_some(tools),
+            stream: is_stream.then_some(true),
+            temperature: self.options.temperature,
+            top_p: self.options.top_p,
+            stop_sequences: self.options.stop.clone(),
+        })
+    }
+
+    fn post(&self, request: &AnthropicRequest) -> Result<reqwest::RequestBuilder, LLMError> {
+        let url = format!("{}/messages", self.config.api_base());
+        debug!(
+            "Anthropic Request: {} {}",
+            url,
+            serde_json::to_string(request)?
+        );
+        Ok(reqwest::Client::new()
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .header("x-api-key", self.config.api_key())
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .body(serde_json::to_string(request)?))
+    }
+}
+
+#[async_trait]
+impl LLM for Anthropic {
+    async fn generate(&self, prompt: &Messages) -> Result<LLMResult, LLMError> {
+        let response = self
+            .post(&self.build_request(prompt, false)?)?
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        debug!("Anthropic Response Body: {:?}", body);
+        if !status.is_success() {
+            return Err(LLMError::OtherError(format!(
+                "Anthropic API error: {} - {}",
+                status, body
+            )));
+        }
+        let response: AnthropicResponse = serde_json::from_str(&body)?;
+        to_result(response.content, Some(to_tokens(&response.usage)))
+    }
+
+    async fn invoke(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        self.generate(messages).await
+    }
+
+    async fn invoke_stream_one_result(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        let mut chunks = self.invoke_stream(messages).await?;
+        let mut generation = String::new();
+        let mut tokens = None;
+        while let Some(chunk) = chunks.next().await {
+            match chunk? {
+                LLMResult::Generate(chunk) => {
+                    generation.push_str(chunk.generation());
+                    if let Some(usage) = chunk.tokens() {
+                        tokens = Some(usage.clone());
+                    }
+                }
+                tool_call => return Ok(tool_call),
+            }
+        }
+        Ok(LLMResult::Generate(GenerateResult::new(generation, tokens)))
+    }
+
+    /// Yields a `LLMResult::Generate` per text delta.
+    /// Tool uses are streamed in pieces, so they are yielded as one `LLMResult::ToolCall` at the end.
+    /// Its AI message also keeps the text streamed before the tool uses.
+    /// The token usage comes with the last result.
+    async fn invoke_stream(&self, messages: &Messages) -> Result<ChatStream, LLMError> {
+        let event_source = self
+            .post(&self.build_request(messages, true)?)?
+            .eventsource()
+            .map_err(|e| LLMError::OtherError(format!("Failed to open event source: {}", e)))?;
+        Ok(chat_stream(stream(event_source).await))
+    }
+
+    fn add_options(&mut self, options: &CallOptions) {
+        self.options = self.options.merge(options);
+    }
+}
+
+/// Splits the system prompts off to the top level and maps the other messages to content blocks.
+/// Tool calls are stored in the OpenAI format in `Message::tool_calls`, and become `tool_use` blocks.
+/// Tool messages become `tool_result` blocks of a user message.
+/// AI messages without text and tool calls are left out, because the API rejects empty content.
+fn to_anthropic_messages(
+    messages: &Messages,
+) -> Result<(Option<String>, Vec<AnthropicMessage>), LLMError> {
+    let mut system = vec![];
+    let mut anthropic_messages: Vec<AnthropicMessage> = vec![];
+    for message in messages.messages.iter() {
+        let content = message.content.clone().unwrap_or_default();
+        let (role, blocks) = match message.message_type {
+            MessageType::SystemMessage => {
+                system.push(content);
+                continue;
+            }
+            MessageType::HumanMessage => ("user", vec![ContentBlock::Text { text: content }]),
+            MessageType::ToolMessage => (
+                "user",
+                vec![ContentBlock::ToolResult {
+                    tool_use_id: message.id.clone().unwrap_or_default(),
+                    content,
+                }],
+            ),
+            MessageType::AIMessage => {
+                let mut blocks = vec![];
+                if !content.is_empty() {
+                    blocks.push(ContentBlock::Text { text: content });
+                }
+                let tool_calls: Vec<ChatCompletionMessageToolCall> = match &message.tool_calls {
+                    Some(tool_calls) => serde_json::from_value(tool_calls.clone())?,
+                    None => vec![],
+                };
+                for tool_call in tool_calls {
+                    blocks.push(ContentBlock::ToolUse {
+                        id: tool_call.id,
+                        name: tool_call.function.name,
+                        input: serde_json::from_str(&tool_call.function.arguments)?,
+                    });
+                }
+                if blocks.is_empty() {
+                    continue;
+                }
+                ("assistant", blocks)
+            }
+        };
+        // 連続する同じ役割のメッセージ (例: 複数のツール結果) は 1 つにまとめる
+        match anthropic_messages.last_mut() {
+            Some(last) if last.role == role => last.content.extend(blocks),
+            _ => anthropic_messages.push(AnthropicMessage {
+                role: role.to_string(),
+                content: blocks,
+            }),
+        }
+    }
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    Ok((system, anthropic_messages))
+}
+
+fn to_tokens(usage: &AnthropicUsage) -> TokenUsage {
+    TokenUsage::new(usage.input_tokens, usage.output_tokens)
+}
+
+/// Texts are joined into the generation. With `tool_use` blocks, the result is a tool call:
+/// `id`, `name` and `arguments` are the ones of the first call,
+/// and the AI message keeps all calls in the OpenAI format for a `ToolNode`.
+fn to_result(
+    content: Vec<ContentBlock>,
+    tokens: Option<TokenUsage>,
+) -> Result<LLMResult, LLMError> {
+    let mut text = String::new();
+    let mut tool_uses = vec![];
+    for block in content {
+        match block {
+            ContentBlock::Text { text: t } => text.push_str(&t),
+            ContentBlock::ToolUse { id, name, input } => tool_uses.push((id, name, input)),
+            _ => {}
+        }
+    }
+    let Some((id, name, arguments)) = tool_uses.first().cloned() else {
+        return Ok(LLMResult::Generate(GenerateResult::new(text, tokens)));
+    };
+    let tool_calls: Vec<Value> = tool_uses
+        .into_iter()
+        .map(|(id, name, input)| {
+            json!({
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": input.to_string() },
+            })
+        })
+        .collect();
+    Ok(LLMResult::ToolCall(ToolCallResult {
+        id,
+        name,
+        arguments,
+        ai_message: Message {
+            content: (!text.is_empty()).then_some(text),
+            message_type: MessageType::AIMessage,
+            tool_calls: Some(Value::Array(tool_calls)),
+            ..Default::default()
+        },
+        tokens,
+        metadata: None,
+    }))
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, LLMError>> + Send>>;
+
+/// A content block whose tool input is still being streamed.
+struct PartialBlock {
+    block: ContentBlock,
+    partial_json: String,
+}
+
+struct StreamState {
+    events: EventStream,
+    /// Text streamed so far, for the AI message of a tool call.
+    text: String,
+    /// Tool use blocks by their index in the message.
+    tool_uses: BTreeMap<usize, PartialBlock>,
+    usage: AnthropicUsage,
+    finished: bool,
+}
+
+impl StreamState {
+    /// The result yielded after the last event.
+    fn finish(&mut self) -> Result<LLMResult, LLMError> {
+        self.finished = true;
+        if self.tool_uses.is_empty() {
+            // テキストは差分として送り済み
+            return to_result(vec![], Some(to_tokens(&self.usage)));
+        }
+        let mut content = vec![ContentBlock::Text {
+            text: std::mem::take(&mut self.text),
+        }];
+        for (_, tool_use) in std::mem::take(&mut self.tool_uses) {
+            let ContentBlock::ToolUse { id, name, .. } = tool_use.block else {
+                continue;
+            };
+            let input = if tool_use.partial_json.is_empty() {
+                json!({})
+            } else {
+                serde_json::from_str(&tool_use.partial_json)?
+            };
+            content.push(ContentBlock::ToolUse { id, name, input });
+        }
+        to_result(content, Some(to_tokens(&self.usage)))
+    }
+}
+
+/// The stream is polled by the caller, so that `emit_token` reaches the token sink of the caller's task.
+fn chat_stream(events: EventStream) -> ChatStream {
+    let state = StreamState {
+        events,
+        text: String::new(),
+        tool_uses: BTreeMap::new(),
+        usage: AnthropicUsage::default(),
+        finished: false,
+    };
+    Box::pin(stream::unfold(state, |mut state| async move {
+        while !state.finished {
+            let event = match state.events.next().await {
+                None | Some(Ok(StreamEvent::MessageStop)) => {
+                    let result = state.finish();
+                    return Some((result, state));
+                }
+                Some(Ok(event)) => event,
+                Some(Err(e)) => return Some((Err(e), state)),
+            };
+            match event {
+                StreamEvent::MessageStart { message } => state.usage = message.usage,
+                StreamEvent::ContentBlockStart {
+                    index,
+                    content_block: block @ ContentBlock::ToolUse { .. },
+                } => {
+                    let partial_json = String::new();
+                    state.tool_uses.insert(
+                        index,
+                        PartialBlock {
+                            block,
+                            partial_json,
+                        },
+                    );
+                }
+                StreamEvent::ContentBlockDelta { index, delta } => match delta {
+                    ContentDelta::TextDelta { text } if !text.is_empty() => {
+                        emit_token(&text);
+                        state.text.push_str(&text);
+                        let result = LLMResult::Generate(GenerateResult::new(text, None));
+                        return Some((Ok(result), state));
+                    }
+                    ContentDelta::InputJsonDelta { partial_json } => {
+                        if let Some(tool_use) = state.tool_uses.get_mut(&index) {
+                            tool_use.partial_json.push_str(&partial_json);
+                        }
+                    }
+                    _ => {}
+                },
+                StreamEvent::MessageDelta { usage, .. } => {
+                    state.usage.output_tokens = usage.output_tokens;
+                }
+                StreamEvent::Error { error } => {
+                    state.finished = true;
+                    let message =
+                        format!("Anthropic API error: {} - {}", error.r#type, error.message);
+                    return Some((Err(LLMError::OtherError(message)), state));
+                }
+                _ => {}
+            }
+        }
+        None
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use futures::StreamExt;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        llm::{
+            MessagesBuilder,
+            anthropic::{AnthropicConfigBuilder, AnthropicModel},
+        },
+        types::openai::{FunctionDescription, Parameters, Tool, ToolType},
+    };
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    fn mock_anthropic_api(status: u16, content_type: &str, body: &str) -> MockServer {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/messages")
+                .header("x-api-key", "test_api_key")
+                .header("anthropic-version", ANTHROPIC_VERSION);
+            then.status(status)
+                .header("content-type", content_type)
+                .body(body);
+        });
+        server
+    }
+
+    fn build_anthropic(api_base: &str) -> Result<Anthropic> {
+        let config = AnthropicConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_api_base(api_base)
+            .with_model(AnthropicModel::Claude35Haiku)
+            .with_max_tokens(1024)
+            .build()?;
+        Ok(Anthropic::new(config))
+    }
+
+    fn weather_tool() -> Tool {
+        Tool {
+            r#type: ToolType::Function,
+            function: FunctionDescription {
+                name: "get_weather".to_string(),
+                description: "Get the current weather in a given location".to_string(),
+                parameters: Parameters {
+                    r#type: "object".to_string(),
+                    properties: HashMap::new(),
+                    required: vec![],
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_build_request() {
+        let anthropic = build_anthropic("http://localhost:8080").unwrap();
+        let tool_calls = json!([
+            { "id": "toolu_1", "type": "function", "function": { "name": "get_weather", "arguments": "{\"location\":\"tokyo\"}" } },
+            { "id": "toolu_2", "type": "function", "function": { "name": "get_weather", "arguments": "{\"location\":\"osaka\"}" } },
+        ]);
+        let mut messages = MessagesBuilder::new()
+            .add_system_message("You are a weather assistant.")
+            .add_human_message("Weather in tokyo and osaka?")
+            .add_tools(vec![weather_tool()])
+            .build();
+        messages.add_message(Message::new_ai_message("Let me check.").with_tool_calls(tool_calls));
+        messages.add_message(Message::new_tool_message("sunny", "toolu_1"));
+        messages.add_message(Message::new_tool_message("rainy", "toolu_2"));
+
+        let request = anthropic.build_request(&messages, false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": "claude-3-5-haiku-latest",
+                "max_tokens": 1024,
+                "system": "You are a weather assistant.",
+                "messages": [
+                    { "role": "user", "content": [{ "type": "text", "text": "Weather in tokyo and osaka?" }] },
+                    { "role": "assistant", "content": [
+                        { "type": "text", "text": "Let me check." },
+                        { "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": { "location": "tokyo" } },
+                        { "type": "tool_use", "id": "toolu_2", "name": "get_weather", "input": { "location": "osaka" } },
+                    ] },
+                    // ツールの結果は 1 つの user メッセージにまとめる
+                    { "role": "user", "content": [
+                        { "type": "tool_result", "tool_use_id": "toolu_1", "content": "sunny" },
+                        { "type": "tool_result", "tool_use_id": "toolu_2", "content": "rainy" },
+                    ] },
+                ],
+                "tools": [{
+                    "name": "get_weather",
+                    "description": "Get the current weather in a given location",
+                    "input_schema": { "type": "object", "properties": {}, "required": [] },
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_request_skips_empty_ai_message() {
+        let anthropic = build_anthropic("http://localhost:8080").unwrap();
+        let mut messages = MessagesBuilder::new().add_human_message("Hello").build();
+        messages.add_message(Message::new_ai_message(""));
+        messages.add_message(Message::new_human_message("Are you there?"));
+
+        let request = anthropic.build_request(&messages, false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request.messages).unwrap(),
+            json!([{ "role": "user", "content": [
+                { "type": "text", "text": "Hello" },
+                { "type": "text", "text": "Are you there?" },
+            ] }])
+        );
+    }
+
+    #[test]
+    fn test_build_request_with_options() {
+        let mut anthropic = build_anthropic("http://localhost:8080").unwrap();
+        anthropic.add_options(
+            &CallOptions::new()
+                .with_temperature(0.5)
+                .with_max_tokens(256)
+                .with_stop(vec!["END".to_string()]),
+        );
+        let messages = MessagesBuilder::new().add_human_message("Hello").build();
+
+        let request = anthropic.build_request(&messages, false).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["stop_sequences"], json!(["END"]));
+        assert!(body.get("top_p").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_invoke() -> Result<()> {
+        init_logger();
+        let body = r#"{"id":"msg_1","type":"message","role":"assistant","model":"claude-3-5-haiku-latest","content":[{"type":"text","text":"Hi there"}],"stop_reason":"end_turn","usage":{"input_tokens":5,"output_tokens":2}}"#;
+        let server = mock_anthropic_api(200, "application/json", body);
+        let anthropic = build_anthropic(&server.url(""))?;
+        let messages = MessagesBuilder::new().add_human_message("Hello").build();
+
+        match anthropic.invoke(&messages).await? {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "Hi there");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_tool_use() -> Result<()> {
+        init_logger();
+        let body = r#"{"id":"msg_2","type":"message","role":"assistant","model":"claude-3-5-haiku-latest","content":[{"type":"text","text":"Let me check."},{"type":"tool_use","id":"toolu_1","name":"get_weather","input":{"location":"tokyo"}}],"stop_reason":"tool_use","usage":{"input_tokens":20,"output_tokens":10}}"#;
+        let server = mock_anthropic_api(200, "application/json", body);
+        let anthropic = build_anthropic(&server.url(""))?;
+        let messages = MessagesBuilder::new().add_human_message("Weather?").build();
+
+        match anthropic.invoke(&messages).await? {
+            LLMResult::ToolCall(result) => {
+                assert_eq!(result.id, "toolu_1");
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(result.ai_message.content.as_deref(), Some("Let me check."));
+                // ToolNode が読めるように OpenAI 形式で保存する
+                let tool_calls: Vec<ChatCompletionMessageToolCall> =
+                    serde_json::from_value(result.ai_message.tool_calls.unwrap())?;
+                assert_eq!(tool_calls[0].function.arguments, r#"{"location":"tokyo"}"#);
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream() -> Result<()> {
+        init_logger();
+        let body = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_3","type":"message","role":"assistant","model":"claude-3-5-haiku-latest","content":[],"stop_reason":null,"usage":{"input_tokens":5,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}
+
+event: ping
+data: {"type":"ping"}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"hello"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":" world"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":2}}
+
+event: message_stop
+data: {"type":"message_stop"}
+
+"#;
+        let server = mock_anthropic_api(200, "text/event-stream", body);
+        let anthropic = build_anthropic(&server.url(""))?;
+        let messages = MessagesBuilder::new().add_human_message("Hello").build();
+
+        let mut stream = anthropic.invoke_stream(&messages).await?;
+        let mut generations = vec![];
+        let mut tokens = None;
+        while let Some(result) = stream.next().await {
+            match result? {
+                LLMResult::Generate(result) => {
+                    generations.push(result.generation().to_string());
+                    tokens = result.tokens().cloned().or(tokens);
+                }
+                _ => panic!("Expected Generate result"),
+            }
+        }
+        assert_eq!(generations, vec!["hello", " world", ""]);
+        assert_eq!(tokens.unwrap().total_tokens, 7);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_tool_use() -> Result<()> {
+        init_logger();
+        let body = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_4","type":"message","role":"assistant","model":"claude-3-5-haiku-latest","content":[],"stop_reason":null,"usage":{"input_tokens":20,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_1","name":"get_weather","input":{}}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"location\":"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":" \"tokyo\"}"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":10}}
+
+event: message_stop
+data: {"type":"message_stop"}
+
+"#;
+        let server = mock_anthropic_api(200, "text/event-stream", body);
+        let anthropic = build_anthropic(&server.url(""))?;
+        let messages = MessagesBuilder::new().add_human_message("Weather?").build();
+
+        match anthropic.invoke_stream_one_result(&messages).await? {
+            LLMResult::ToolCall(result) => {
+                assert_eq!(result.id, "toolu_1");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(result.tokens.unwrap().total_tokens, 30);
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_text_and_tool_use() -> Result<()> {
+        init_logger();
+        let body = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_5","type":"message","role":"assistant","model":"claude-3-5-haiku-latest","content":[],"stop_reason":null,"usage":{"input_tokens":20,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me check"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":" the weather."}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_2","name":"get_weather","input":{}}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\": \"tokyo\"}"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":1}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":15}}
+
+event: message_stop
+data: {"type":"message_stop"}
+
+"#;
+        let server = mock_anthropic_api(200, "text/event-stream", body);
+        let anthropic = build_anthropic(&server.url(""))?;
+        let messages = MessagesBuilder::new().add_human_message("Weather?").build();
+
+        let results: Vec<LLMResult> = anthropic
+            .invoke_stream(&messages)
+            .await?
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        assert_eq!(results.len(), 3);
+        match results.last().unwrap() {
+            LLMResult::ToolCall(result) => {
+                assert_eq!(result.id, "toolu_2");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(
+                    result.ai_message.content.as_deref(),
+                    Some("Let me check the weather.")
+                );
+                assert_eq!(result.tokens.as_ref().unwrap().total_tokens, 35);
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use futures::{StreamExt, stream};
+use serde_json::{Value, json};
+
+use super::{
+    CallOptions, ChatStream, GenerateResult, LLM, LLMError, LLMResult, Message, Messages,
+    ToolCallResult, emit_token,
+};
+
+#[derive(Default)]
+struct FakeState {
+    /// The chunks of each response, or the message of a failure.
+    responses: VecDeque<Result<Vec<LLMResult>, String>>,
+    requests: Vec<Messages>,
+}
+
+/// An `LLM` that returns scripted responses in order, for tests of agents and graphs without a server.
+///
+/// Each call takes the next response of the script, and fails when the script is used up.
+/// Clones share the script and the recorded requests, so a clone can be handed to a node
+/// and the other one kept for assertions.
+///
+/// ```rust,ignore
+/// let llm = FakeLLM::new()
+///     .with_tool_call("get_weather", json!({ "location": "tokyo" }))
+///     .with_generation("It is sunny.");
+/// let node = ChatModelNode::new("agent", llm.clone()).with_tool(&WeatherTool);
+/// // ... グラフを実行した後
+/// assert_eq!(llm.requests().len(), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct FakeLLM {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl FakeLLM {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(self, response: Result<Vec<LLMResult>, String>) -> Self {
+        self.state.lock().unwrap().responses.push_back(response);
+        self
+    }
+
+    /// Appends a result. `invoke_stream` yields it as a single chunk.
+    pub fn with_result(self, result: LLMResult) -> Self {
+        self.push(Ok(vec![result]))
+    }
+
+    /// Appends a text generation.
+    pub fn with_generation(self, generation: &str) -> Self {
+        let result = GenerateResult::new(generation.to_string(), None);
+        self.with_result(LLMResult::Generate(result))
+    }
+
+    /// Appends a call of the tool `name`. The AI message carries it in the OpenAI format,
+    /// so a `ToolNode` can run it. The id is `call_{n}` with `n` the position in the script.
+    pub fn with_tool_call(self, name: &str, arguments: Value) -> Self {
+        let id = format!("call_{}", self.state.lock().unwrap().responses.len());
+        let tool_calls = json!([{
+            "id": id,
+            "type": "function",
+            "function": { "name": name, "arguments": arguments.to_string() },
+        }]);
+        self.with_result(LLMResult::ToolCall(ToolCallResult {
+            id,
+            name: name.to_string(),
+            arguments,
+            ai_message: Message::new_ai_message("").with_tool_calls(tool_calls),
+            ..Default::default()
+        }))
+    }
+
+    /// Appends chunks that `invoke_stream` yields one by one.
+    /// The other methods merge them like the providers do: texts are joined, a tool call wins,
+    /// and the token usage of the last chunk is kept.
+    pub fn with_chunks(self, chunks: Vec<LLMResult>) -> Self {
+        self.push(Ok(chunks))
+    }
+
+    /// Appends a failure, returned as `LLMError::OtherError`.
+    pub fn with_error(self, message: &str) -> Self {
+        self.push(Err(message.to_string()))
+    }
+
+    /// Every `Messages` received so far, in order.
+    pub fn requests(&self) -> Vec<Messages> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Number of scripted responses not used yet.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().responses.len()
+    }
+
+    /// Records `messages` and takes the chunks of the next response.
+    fn next_chunks(&self, messages: &Messages) -> Result<Vec<LLMResult>, LLMError> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(messages.clone());
+        match state.responses.pop_front() {
+            Some(response) => response.map_err(LLMError::OtherError),
+            None => Err(LLMError::OtherError(
+                "FakeLLM has no scripted response left".to_string(),
+            )),
+        }
+    }
+}
+
+/// A single chunk is returned as it is.
+fn merge_chunks(mut chunks: Vec<LLMResult>) -> LLMResult {
+    if chunks.len() == 1 {
+        return chunks.remove(0);
+    }
+    let mut generation = String::new();
+    let mut tokens = None;
+    for chunk in chunks {
+        match chunk {
+            LLMResult::Generate(chunk) => {
+                generation.push_str(chunk.generation());
+                tokens = chunk.tokens().cloned().or(tokens);
+            }
+            tool_call => return tool_call,
+        }
+    }
+    LLMResult::Generate(GenerateResult::new(generation, tokens))
+}
+
+#[async_trait]
+impl LLM for FakeLLM {
+    async fn generate(&self, prompt: &Messages) -> Result<LLMResult, LLMError> {
+        Ok(merge_chunks(self.next_chunks(prompt)?))
+    }
+
+    async fn invoke(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        self.generate(messages).await
+    }
+
+    async fn invoke_stream_one_result(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        self.generate(messages).await
+    }
+
+    /// Texts of the chunks are passed to `emit_token` as they are polled.
+    async fn invoke_stream(&self, messages: &Messages) -> Result<ChatStream, LLMError> {
+        let chunks = self.next_chunks(messages)?;
+        Ok(Box::pin(stream::iter(chunks).map(|chunk| {
+            if let LLMResult::Generate(chunk) = &chunk {
+                emit_token(chunk.generation());
+            }
+            Ok(chunk)
+        })))
+    }
+
+    fn add_options(&mut self, _options: &CallOptions) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Result;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        llm::{MessagesBuilder, TokenSink, with_token_sink},
+        types::TokenUsage,
+    };
+
+    fn chunk(text: &str, tokens: Option<TokenUsage>) -> LLMResult {
+        LLMResult::Generate(GenerateResult::new(text.to_string(), tokens))
+    }
+
+    #[tokio::test]
+    async fn test_fake_llm_script() -> Result<()> {
+        let llm = FakeLLM::new()
+            .with_tool_call("get_weather", json!({ "location": "tokyo" }))
+            .with_error("rate limited")
+            .with_generation("It is sunny.");
+        let handle = llm.clone();
+        let messages = MessagesBuilder::new().add_human_message("weather?").build();
+
+        match llm.invoke(&messages).await? {
+            LLMResult::ToolCall(result) => {
+                assert_eq!(result.id, "call_0");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                let tool_calls = result.ai_message.tool_calls.unwrap();
+                assert_eq!(
+                    tool_calls[0]["function"]["arguments"],
+                    r#"{"location":"tokyo"}"#
+                );
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        assert!(matches!(
+            llm.invoke(&messages).await,
+            Err(LLMError::OtherError(message)) if message == "rate limited"
+        ));
+        let result = llm.invoke(&messages).await?;
+        assert_eq!(result.ai_message().content.as_deref(), Some("It is sunny."));
+        assert!(llm.invoke(&messages).await.is_err());
+
+        // clone からも受け取ったメッセージが見える
+        let requests = handle.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].messages[0].content.as_deref(), Some("weather?"));
+        assert_eq!(handle.remaining(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fake_llm_stream() -> Result<()> {
+        let chunks = vec![
+            chunk("hello", None),
+            chunk(" world", None),
+            chunk("", Some(TokenUsage::new(5, 2))),
+        ];
+        let llm = FakeLLM::new()
+            .with_chunks(chunks.clone())
+            .with_chunks(chunks);
+        let messages = MessagesBuilder::new().add_human_message("Hello").build();
+
+        let tokens = Arc::new(Mutex::new(String::new()));
+        let sink_tokens = tokens.clone();
+        let sink: TokenSink = Arc::new(move |token| sink_tokens.lock().unwrap().push_str(token));
+        let generations = with_token_sink(sink, async {
+            let mut generations = vec![];
+            let mut stream = llm.invoke_stream(&messages).await?;
+            while let Some(result) = stream.next().await {
+                generations.push(result?.ai_message().content.unwrap());
+            }
+            Ok::<_, LLMError>(generations)
+        })
+        .await?;
+        assert_eq!(generations, vec!["hello", " world", ""]);
+        assert_eq!(*tokens.lock().unwrap(), "hello world");
+
+        match llm.invoke_stream_one_result(&messages).await? {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "hello world");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,49 @@
+use std::fmt;
+
 use anyhow::Result;
 
+use super::GeminiSafetySetting;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum GeminiModel {
     Gemini15,
     Gemini20,
 }
 
-impl ToString for GeminiModel {
-    fn to_string(&self) -> String {
+impl fmt::Display for GeminiModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            GeminiModel::Gemini15 => "gemini-1.5-flash".to_string(),
-            GeminiModel::Gemini20 => "gemini-2.0-flash-001".to_string(),
+            GeminiModel::Gemini15 => write!(f, "gemini-1.5-flash"),
+            GeminiModel::Gemini20 => write!(f, "gemini-2.0-flash-001"),
         }
     }
 }
 
-impl Into<String> for GeminiModel {
-    fn into(self) -> String {
-        self.to_string()
+impl From<GeminiModel> for String {
+    fn from(val: GeminiModel) -> Self {
+        val.to_string()
+    }
+}
+
+/// The API that `Gemini` talks to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum GeminiBackend {
+    /// The OpenAI compatible `chat/completions` endpoint.
+    #[default]
+    OpenAICompatible,
+    /// The native `models/{model}:generateContent` endpoint.
+    /// Needed for safety settings, cached content and grounding, and the results carry `GeminiMetadata`.
+    Native,
+}
+
+impl GeminiBackend {
+    fn default_api_base(&self) -> &'static str {
+        match self {
+            GeminiBackend::OpenAICompatible => {
+                "https://generativelanguage.googleapis.com/v1beta/openai"
+            }
+            GeminiBackend::Native => "https://generativelanguage.googleapis.com/v1beta",
+        }
     }
 }
 
@@ -26,14 +52,23 @@ pub struct GeminiConfig {
     api_base: String,
     api_key: String,
     model: GeminiModel,
+    backend: GeminiBackend,
+    safety_settings: Vec<GeminiSafetySetting>,
+    cached_content: Option<String>,
+    google_search: bool,
 }
 
 impl Default for GeminiConfig {
     fn default() -> Self {
+        let backend = GeminiBackend::default();
         Self {
-            api_base: "https://generativelanguage.googleapis.com/v1beta/openai".to_string(),
+            api_base: backend.default_api_base().to_string(),
             api_key: "".to_string(),
             model: GeminiModel::Gemini15,
+            backend,
+            safety_settings: vec![],
+            cached_content: None,
+            google_search: false,
         }
     }
 }
@@ -48,12 +83,30 @@ impl GeminiConfig {
     pub fn model(&self) -> &GeminiModel {
         &self.model
     }
+    pub fn backend(&self) -> &GeminiBackend {
+        &self.backend
+    }
+    pub fn safety_settings(&self) -> &[GeminiSafetySetting] {
+        &self.safety_settings
+    }
+    pub fn cached_content(&self) -> Option<&str> {
+        self.cached_content.as_deref()
+    }
+    pub fn google_search(&self) -> bool {
+        self.google_search
+    }
 }
 
 pub struct GeminiConfigBuilder {
     config: GeminiConfig,
 }
 
+impl Default for GeminiConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GeminiConfigBuilder {
     pub fn new() -> Self {
         Self {
@@ -72,10 +125,44 @@ impl GeminiConfigBuilder {
         self.config.model = model;
         self
     }
+    /// The api base follows the backend unless it was set with `with_api_base`.
+    pub fn with_backend(mut self, backend: GeminiBackend) -> Self {
+        if self.config.api_base == self.config.backend.default_api_base() {
+            self.config.api_base = backend.default_api_base().into();
+        }
+        self.config.backend = backend;
+        self
+    }
+    /// Needs `GeminiBackend::Native`.
+    pub fn with_safety_setting(mut self, category: &str, threshold: &str) -> Self {
+        self.config.safety_settings.push(GeminiSafetySetting {
+            category: category.into(),
+            threshold: threshold.into(),
+        });
+        self
+    }
+    /// Name of a cached content, e.g. `cachedContents/abc123`. Needs `GeminiBackend::Native`.
+    pub fn with_cached_content(mut self, name: &str) -> Self {
+        self.config.cached_content = Some(name.into());
+        self
+    }
+    /// Grounds the answers with Google Search. Needs `GeminiBackend::Native`.
+    pub fn with_google_search(mut self) -> Self {
+        self.config.google_search = true;
+        self
+    }
     pub fn build(self) -> Result<GeminiConfig> {
         if self.config.api_key.is_empty() {
             anyhow::bail!("API key must be set");
         }
+        let native_only = !self.config.safety_settings.is_empty()
+            || self.config.cached_content.is_some()
+            || self.config.google_search;
+        if native_only && self.config.backend != GeminiBackend::Native {
+            anyhow::bail!(
+                "Safety settings, cached content and Google Search need GeminiBackend::Native"
+            );
+        }
 
         Ok(self.config)
     }
@@ -90,7 +177,7 @@ mod tests {
     fn test_gemini_config_builder_api_key_empty() {
         let result = GeminiConfigBuilder::new().build();
         match result {
-            Ok(_) => assert!(false),
+            Ok(_) => panic!("API key must be required"),
             Err(err) => assert_eq!(err.to_string(), "API key must be set"),
         }
     }
@@ -108,4 +195,36 @@ mod tests {
         assert_eq!(config.api_key, "test_api_key");
         assert_eq!(config.model, GeminiModel::Gemini20);
     }
+
+    #[test]
+    fn test_gemini_config_builder_native_backend() {
+        let config = GeminiConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_backend(GeminiBackend::Native)
+            .with_safety_setting("HARM_CATEGORY_HARASSMENT", "BLOCK_ONLY_HIGH")
+            .with_google_search()
+            .build()
+            .unwrap();
+        assert_eq!(
+            config.api_base,
+            "https://generativelanguage.googleapis.com/v1beta"
+        );
+        assert_eq!(config.safety_settings.len(), 1);
+
+        // 明示的に設定した api base はそのまま
+        let config = GeminiConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_api_base("https://example.com")
+            .with_backend(GeminiBackend::Native)
+            .build()
+            .unwrap();
+        assert_eq!(config.api_base, "https://example.com");
+
+        // ネイティブ API 専用の設定
+        let result = GeminiConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_cached_content("cachedContents/abc123")
+            .build();
+        assert!(result.is_err());
+    }
 }
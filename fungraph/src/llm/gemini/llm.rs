@@ -10,15 +10,12 @@ use log::{debug, warn};
 use anyhow::Result;
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use reqwest_eventsource::{Event, EventSource, RequestBuilderExt};
-use serde::de::DeserializeOwned;
-use serde_json::Value;
 
+pub use crate::types::openai::OpenAIMessages;
 use crate::{
     llm::{
-        CallOptions, GenerateResult, LLM, LLMError, LLMResult, Message, MessageType, Messages,
-        ToolCallResult,
-        gemini::{GeminiResponse, OpenAIContent},
-        messages,
+        CallOptions, ChatStream, GenerateResult, LLM, LLMError, LLMResult, Message, MessageType,
+        Messages, ToolCallResult, emit_token, gemini::GeminiResponse, sse::stream,
     },
     types::{
         TokenUsage,
@@ -29,7 +26,7 @@ use crate::{
     },
 };
 
-use super::{GeminiConfig, GeminiRequest};
+use super::{GeminiBackend, GeminiConfig, GeminiRequest, native};
 
 #[derive(Clone)]
 pub struct Gemini {
@@ -50,12 +47,15 @@ impl Gemini {
         self
     }
 }
-// open ai互換のgeminiを使う
+// open ai互換のgeminiを使う (GeminiBackend::Native の場合は native.rs)
 // https://developers.googleblog.com/en/gemini-is-now-accessible-from-the-openai-library/
 
 #[async_trait]
 impl LLM for Gemini {
     async fn generate(&self, prompt: &Messages) -> Result<LLMResult, LLMError> {
+        if self.config.backend() == &GeminiBackend::Native {
+            return native::generate(&self.config, &self.options, prompt).await;
+        }
         let gemini_request = self.build_gemini_request_no_stream(prompt)?;
         let client = reqwest::Client::new();
         let url = format!("{}/chat/completions", self.config.api_base());
@@ -76,14 +76,18 @@ impl LLM for Gemini {
 
         if status.is_success() {
             let gemini_response: GeminiResponse = serde_json::from_str(&body_json)?;
-            let mut generate_result = GenerateResult::default();
+            let tokens = gemini_response.usage.as_ref().map(|usage| TokenUsage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            });
+            let mut generate_result = GenerateResult::new(String::new(), tokens.clone());
             let mut result = LLMResult::Generate(generate_result.clone());
             if let Some(choice) = gemini_response.choices.first() {
                 let finish_reason = choice.finish_reason.unwrap();
                 match finish_reason {
                     FinishReason::ToolCalls => {
                         let choice = choice.clone();
-                        let assistant_message = choice.message.clone();
                         let name = choice
                             .message
                             .tool_calls
@@ -124,19 +128,21 @@ impl LLM for Gemini {
                             name,
                             arguments,
                             ai_message: Message {
-                                content: Some("tool called".into()),
+                                content: choice.message.content.clone(),
                                 message_type: MessageType::AIMessage,
                                 id: None,
                                 tool_calls: Some(tool_calls),
                                 images: None,
                                 name: None,
                             },
+                            tokens,
+                            metadata: None,
                         });
                     }
                     _ => {
-                        choice.message.content.as_ref().map(|content| {
+                        if let Some(content) = choice.message.content.as_ref() {
                             generate_result.set_generation(content);
-                        });
+                        }
                         result = LLMResult::Generate(generate_result);
                     }
                 }
@@ -155,6 +161,9 @@ impl LLM for Gemini {
     }
 
     async fn invoke_stream_one_result(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        if self.config.backend() == &GeminiBackend::Native {
+            return native::invoke_stream_one_result(&self.config, &self.options, messages).await;
+        }
         debug!("message: {:?}", messages.messages);
 
         let client = reqwest::Client::new();
@@ -191,12 +200,7 @@ impl LLM for Gemini {
                         match finish_reason {
                             FinishReason::ToolCalls => {
                                 if let Some(tool_calls) = chat_choice.delta.tool_calls {
-                                    let data = tool_calls.iter().for_each(|tool_call| {
-                                        let id = &tool_call.id;
-                                        let tool_call_type = &tool_call.r#type;
-                                        let function = &tool_call.function;
-                                        let index = &tool_call.index;
-                                    });
+                                    debug!("tool_calls: {:?}", tool_calls);
                                 }
                             }
                             _ => {
@@ -216,6 +220,9 @@ impl LLM for Gemini {
     }
 
     async fn invoke_stream(&self, messages: &Messages) -> Result<ChatStream, LLMError> {
+        if self.config.backend() == &GeminiBackend::Native {
+            return native::invoke_stream(&self.config, &self.options, messages).await;
+        }
         let client = reqwest::Client::new();
         let url = format!("{}/chat/completions", self.config.api_base());
 
@@ -228,75 +235,25 @@ impl LLM for Gemini {
             .body(serde_json::to_string(&request)?)
             .eventsource()
             .unwrap();
-        Ok(ChatStream::new(event_source))
+        Ok(Box::pin(GeminiChatStream::new(event_source)))
     }
 
     fn add_options(&mut self, options: &CallOptions) {
-        self.options.merge(options);
+        self.options = self.options.merge(options);
     }
 }
 
-pub(crate) async fn stream<O>(
-    mut event_source: EventSource,
-) -> Pin<Box<dyn Stream<Item = Result<O, LLMError>> + Send>>
-where
-    O: DeserializeOwned + std::marker::Send + 'static,
-{
-    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-    tokio::spawn(async move {
-        while let Some(ev) = event_source.next().await {
-            match ev {
-                Err(e) => {
-                    if let Err(_e) = tx.send(Err(LLMError::OtherError(format!(
-                        "Event source error: {}",
-                        e.to_string()
-                    )))) {
-                        // rx dropped
-                        break;
-                    }
-                }
-                Ok(event) => match event {
-                    Event::Message(message) => {
-                        if message.data == "[DONE]" {
-                            break;
-                        }
-
-                        let response = match serde_json::from_str::<O>(&message.data) {
-                            Err(e) => Err(LLMError::OtherError(format!(
-                                "serde_json error: {}",
-                                e.to_string()
-                            ))),
-                            Ok(output) => Ok(output),
-                        };
-
-                        if let Err(_e) = tx.send(response) {
-                            // rx dropped
-                            break;
-                        }
-                    }
-                    Event::Open => continue,
-                },
-            }
-        }
-
-        event_source.close();
-    });
-
-    Box::pin(tokio_stream::wrappers::UnboundedReceiverStream::new(rx))
-}
-
-pub struct ChatStream {
+pub struct GeminiChatStream {
     event_source: EventSource,
 }
 
-impl ChatStream {
+impl GeminiChatStream {
     pub fn new(event_source: EventSource) -> Self {
         Self { event_source }
     }
 }
 
-impl Stream for ChatStream {
+impl Stream for GeminiChatStream {
     type Item = Result<LLMResult, LLMError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
@@ -335,81 +292,63 @@ impl Stream for ChatStream {
                                                 total_tokens: usage.total_tokens,
                                             });
                                         }
-                                        let mut generation = String::new();
                                         if let Some(choice) = response.choices.first() {
-                                            if let Some(finish_reason) = &choice.finish_reason {
-                                                match finish_reason {
-                                                    FinishReason::ToolCalls => {
-                                                        let choice = choice.clone();
-                                                        let name = (&choice
-                                                            .delta
-                                                            .tool_calls
-                                                            .clone()
-                                                            .unwrap()
-                                                            .first()
-                                                            .unwrap()
-                                                            .function
-                                                            .clone()
-                                                            .unwrap()
-                                                            .name
-                                                            .unwrap())
-                                                            .to_string();
-                                                        let arguments = serde_json::from_str(
-                                                            &choice
-                                                                .clone()
-                                                                .delta
-                                                                .tool_calls
-                                                                .unwrap()
-                                                                .first()
-                                                                .unwrap()
-                                                                .function
-                                                                .clone()
-                                                                .unwrap()
-                                                                .arguments
-                                                                .unwrap(),
-                                                        )
-                                                        .unwrap();
-                                                        let tool_calls = serde_json::to_value(
-                                                            &choice.clone().delta.tool_calls,
-                                                        )
-                                                        .unwrap();
-
-                                                        Ok(LLMResult::ToolCall(ToolCallResult {
-                                                            id: "".to_string(),
-                                                            name,
-                                                            arguments,
-                                                            ai_message: Message {
-                                                                content: Some("tool called".into()),
-                                                                message_type:
-                                                                    MessageType::AIMessage,
-                                                                id: None,
-                                                                tool_calls: Some(tool_calls),
-                                                                images: None,
-                                                                name: None,
-                                                            },
-                                                        }))
-                                                    }
-                                                    _ => {
-                                                        // func a
-                                                        if let Some(content) = &choice.delta.content
-                                                        {
-                                                            Ok(LLMResult::Generate(
-                                                                GenerateResult::new(
-                                                                    content.clone(),
-                                                                    tokens,
-                                                                ),
-                                                            ))
-                                                        } else {
-                                                            Err(LLMError::OtherError(
-                                                                "No content in response"
-                                                                    .to_string(),
-                                                            ))
-                                                        }
-                                                    }
-                                                }
+                                            if let Some(FinishReason::ToolCalls) =
+                                                &choice.finish_reason
+                                            {
+                                                let choice = choice.clone();
+                                                let name = choice
+                                                    .delta
+                                                    .tool_calls
+                                                    .clone()
+                                                    .unwrap()
+                                                    .first()
+                                                    .unwrap()
+                                                    .function
+                                                    .clone()
+                                                    .unwrap()
+                                                    .name
+                                                    .unwrap()
+                                                    .to_string();
+                                                let arguments = serde_json::from_str(
+                                                    &choice
+                                                        .clone()
+                                                        .delta
+                                                        .tool_calls
+                                                        .unwrap()
+                                                        .first()
+                                                        .unwrap()
+                                                        .function
+                                                        .clone()
+                                                        .unwrap()
+                                                        .arguments
+                                                        .unwrap(),
+                                                )
+                                                .unwrap();
+                                                let tool_calls = serde_json::to_value(
+                                                    &choice.clone().delta.tool_calls,
+                                                )
+                                                .unwrap();
+
+                                                Ok(LLMResult::ToolCall(ToolCallResult {
+                                                    id: "".to_string(),
+                                                    name,
+                                                    arguments,
+                                                    ai_message: Message {
+                                                        content: choice.delta.content.clone(),
+                                                        message_type: MessageType::AIMessage,
+                                                        id: None,
+                                                        tool_calls: Some(tool_calls),
+                                                        images: None,
+                                                        name: None,
+                                                    },
+                                                    tokens,
+                                                    metadata: None,
+                                                }))
                                             } else {
                                                 // func a
                                                 if let Some(content) = &choice.delta.content {
+                                                    emit_token(content);
                                                     Ok(LLMResult::Generate(GenerateResult::new(
                                                         content.clone(),
                                                         tokens,
@@ -450,40 +389,6 @@ impl Stream for ChatStream {
     }
 }
 
-pub trait OpenAIMessages {
-    fn to_openai_messages(&self) -> Vec<OpenAIContent>;
-    fn to_json_value(&self) -> Value;
-}
-
-impl OpenAIMessages for Messages {
-    fn to_openai_messages(&self) -> Vec<OpenAIContent> {
-        let mut contents: Vec<OpenAIContent> = Vec::new();
-        for message in self.messages.iter() {
-            let role = match message.message_type {
-                MessageType::AIMessage => "assistant",
-                MessageType::HumanMessage => "user",
-                MessageType::SystemMessage => "system",
-                MessageType::ToolMessage => "tool",
-            }
-            .to_string();
-            let tool_calls = message.tool_calls.clone();
-            let gemini_message = OpenAIContent {
-                content: message.content.clone(),
-                role,
-                tool_calls,
-                tool_call_id: message.id.clone(),
-            };
-            contents.push(gemini_message);
-        }
-        contents
-    }
-
-    fn to_json_value(&self) -> Value {
-        let mut contents: Vec<OpenAIContent> = self.to_openai_messages();
-        serde_json::to_value(contents).unwrap()
-    }
-}
-
 impl Gemini {
     fn build_gemini_request(
         &self,
@@ -512,6 +417,7 @@ impl Gemini {
             stream,
             tools,
             tool_choice,
+            options: self.options.clone(),
         };
         debug!(
             "Gemini Request json: {:?}",
@@ -534,12 +440,16 @@ impl Gemini {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
 
     use crate::{
         llm::{
-            LLM, LLMResult, Messages, MessagesBuilder,
+            CallOptions, LLM, LLMError, LLMResult, Messages, MessagesBuilder, TokenSink,
             gemini::{Gemini, GeminiConfigBuilder, GeminiModel},
+            with_token_sink,
         },
         types::openai::Tool,
     };
@@ -600,6 +510,17 @@ mod tests {
         assert_eq!(request.model, "gemini-2.0-flash-001");
     }
 
+    #[test]
+    fn test_build_gemini_request_with_options() {
+        let mut gemini = build_gemini(GeminiModel::Gemini20);
+        gemini.add_options(&CallOptions::new().with_temperature(0.5));
+        let messages: Messages = MessagesBuilder::new().add_human_message("Hello").build();
+        let request = gemini.build_gemini_request_no_stream(&messages).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["temperature"], 0.5);
+        assert!(body.get("max_tokens").is_none());
+    }
+
     #[test]
     fn test_build_gemini_request_with_tools() {
         let gemini = build_gemini(GeminiModel::Gemini20);
@@ -732,6 +653,42 @@ data: [DONE]
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_invoke_stream_emits_tokens() -> Result<()> {
+        init_logger();
+
+        let body = r#"
+data: {"choices":[{"delta":{"content":"hello"},"finish_reason":null,"index":0}],"created":1677667095,"model":"gpt-3.5-turbo-0301","object":"chat.completion.chunk"}
+
+data: {"choices":[{"delta":{"content":" world"},"finish_reason":null,"index":0}],"created":1677667095,"model":"gpt-3.5-turbo-0301","object":"chat.completion.chunk"}
+
+data: [DONE]
+"#;
+
+        let server = mock_gemini_stream_api(200, body);
+        let config = GeminiConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_api_base(&server.url(""))
+            .build()?;
+        let gemini = Gemini::new(config);
+        let messages: Messages = MessagesBuilder::new().add_human_message("Hi").build();
+
+        let tokens = Arc::new(Mutex::new(String::new()));
+        let sink_tokens = tokens.clone();
+        let sink: TokenSink = Arc::new(move |token| sink_tokens.lock().unwrap().push_str(token));
+        with_token_sink(sink, async {
+            let mut stream = gemini.invoke_stream(&messages).await?;
+            while let Some(result) = stream.next().await {
+                result?;
+            }
+            Ok::<(), LLMError>(())
+        })
+        .await?;
+
+        assert_eq!(*tokens.lock().unwrap(), "hello world");
+        Ok(())
+    }
+
     // RUST_LOG=debug cargo test llm::gemini::llm::tests::test_invoke_stream_tool_calls
     #[tokio::test]
     async fn test_invoke_stream_tool_calls() -> Result<()> {
@@ -762,10 +719,10 @@ data: [DONE]
                 LLMResult::ToolCall(delta) => {
                     assert_eq!(delta.name, "get_current_weather");
                 }
-                _ => assert!(false, "Expected Stream result"),
+                _ => panic!("Expected Stream result"),
             }
         } else {
-            assert!(false, "Expected Stream result");
+            panic!("Expected Stream result");
         }
 
         Ok(())
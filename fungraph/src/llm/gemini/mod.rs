@@ -4,3 +4,9 @@ pub mod config;
 pub use config::*;
 pub mod llm;
 pub use llm::*;
+mod native;
+
+/// Former name of `GeminiChatStream`.
+/// `LLM::invoke_stream` returns the boxed `crate::llm::ChatStream` for every provider.
+#[deprecated(note = "renamed to `GeminiChatStream`")]
+pub type ChatStream = GeminiChatStream;
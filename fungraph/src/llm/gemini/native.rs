@@ -0,0 +1,681 @@
+use std::{collections::HashMap, pin::Pin};
+
+use futures::{Stream, StreamExt, stream};
+use log::debug;
+use reqwest::header::CONTENT_TYPE;
+use reqwest_eventsource::RequestBuilderExt;
+use serde_json::json;
+
+use crate::{
+    llm::{
+        CallOptions, ChatStream, GenerateResult, LLMError, LLMResult, Message, MessageType,
+        Messages, ToolCallResult, emit_token, new_tool_call_id, sse::stream,
+    },
+    types::{
+        TokenUsage,
+        openai::{ChatCompletionMessageToolCall, ChatCompletionToolType, FunctionCall},
+    },
+};
+
+use super::{
+    GeminiConfig, GeminiContent, GeminiFunctionCall, GeminiFunctionResponse,
+    GeminiGenerationConfig, GeminiInlineData, GeminiMetadata, GeminiNativeRequest,
+    GeminiNativeResponse, GeminiPart, GeminiTool,
+};
+
+// ネイティブの generateContent API を使う
+// https://ai.google.dev/api/generate-content
+
+pub(super) async fn generate(
+    config: &GeminiConfig,
+    options: &CallOptions,
+    messages: &Messages,
+) -> Result<LLMResult, LLMError> {
+    let request = build_request(config, options, messages)?;
+    let response = post(config, &request, "generateContent")?.send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    debug!("Gemini Response Body: {:?}", body);
+    if !status.is_success() {
+        return Err(LLMError::OtherError(format!(
+            "Gemini API error: {} - {}",
+            status, body
+        )));
+    }
+    let mut response = Response::default();
+    let text = response.update(serde_json::from_str(&body)?);
+    response.into_result(text)
+}
+
+pub(super) async fn invoke_stream_one_result(
+    config: &GeminiConfig,
+    options: &CallOptions,
+    messages: &Messages,
+) -> Result<LLMResult, LLMError> {
+    let mut chunks = invoke_stream(config, options, messages).await?;
+    let mut generation = String::new();
+    let mut last = GenerateResult::default();
+    while let Some(chunk) = chunks.next().await {
+        match chunk? {
+            LLMResult::Generate(chunk) => {
+                generation.push_str(chunk.generation());
+                last = chunk;
+            }
+            tool_call => return Ok(tool_call),
+        }
+    }
+    // 最後の結果がトークン数とメタデータを持つ
+    let mut result = GenerateResult::new(generation, last.tokens().cloned());
+    if let Some(metadata) = last.metadata() {
+        result = result.with_metadata(metadata.clone());
+    }
+    Ok(LLMResult::Generate(result))
+}
+
+/// Yields a `LLMResult::Generate` per text part.
+/// Function calls are collected and yielded as one `LLMResult::ToolCall` at the end.
+/// The token usage and `GeminiMetadata` come with the last result.
+pub(super) async fn invoke_stream(
+    config: &GeminiConfig,
+    options: &CallOptions,
+    messages: &Messages,
+) -> Result<ChatStream, LLMError> {
+    let event_source = post(
+        config,
+        &build_request(config, options, messages)?,
+        "streamGenerateContent?alt=sse",
+    )?
+    .eventsource()
+    .map_err(|e| LLMError::OtherError(format!("Failed to open event source: {}", e)))?;
+    Ok(chat_stream(stream(event_source).await))
+}
+
+fn post(
+    config: &GeminiConfig,
+    request: &GeminiNativeRequest,
+    method: &str,
+) -> Result<reqwest::RequestBuilder, LLMError> {
+    let url = format!("{}/models/{}:{}", config.api_base(), config.model(), method);
+    debug!(
+        "Gemini Request: {} {}",
+        url,
+        serde_json::to_string(request)?
+    );
+    Ok(reqwest::Client::new()
+        .post(&url)
+        .header(CONTENT_TYPE, "application/json")
+        .header("x-goog-api-key", config.api_key())
+        .body(serde_json::to_string(request)?))
+}
+
+pub(super) fn build_request(
+    config: &GeminiConfig,
+    options: &CallOptions,
+    messages: &Messages,
+) -> Result<GeminiNativeRequest, LLMError> {
+    let (system_instruction, contents) = to_contents(messages)?;
+    let mut tools = vec![];
+    if !messages.tools.is_empty() {
+        let declarations = messages.tools.iter().map(|tool| tool.function.clone());
+        tools.push(GeminiTool {
+            function_declarations: Some(declarations.collect()),
+            google_search: None,
+        });
+    }
+    if config.google_search() {
+        tools.push(GeminiTool {
+            function_declarations: None,
+            google_search: Some(json!({})),
+        });
+    }
+    Ok(GeminiNativeRequest {
+        contents,
+        system_instruction,
+        tools,
+        safety_settings: config.safety_settings().to_vec(),
+        cached_content: config.cached_content().map(String::from),
+        generation_config: to_generation_config(options),
+    })
+}
+
+/// Returns `None` when no option is set, so the model defaults apply.
+fn to_generation_config(options: &CallOptions) -> Option<GeminiGenerationConfig> {
+    let generation_config = GeminiGenerationConfig {
+        temperature: options.temperature,
+        max_output_tokens: options.max_tokens,
+        top_p: options.top_p,
+        stop_sequences: options.stop.clone(),
+    };
+    (generation_config != GeminiGenerationConfig::default()).then_some(generation_config)
+}
+
+/// System messages become the `systemInstruction`.
+/// Tool calls are stored in the OpenAI format in `Message::tool_calls`, and become `functionCall` parts.
+/// A tool message becomes a `functionResponse` part, named after its call found by `Message::id`.
+fn to_contents(
+    messages: &Messages,
+) -> Result<(Option<GeminiContent>, Vec<GeminiContent>), LLMError> {
+    let mut system = vec![];
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    let mut contents: Vec<GeminiContent> = vec![];
+    for message in messages.messages.iter() {
+        let content = message.content.clone().unwrap_or_default();
+        let text = |text: String| GeminiPart {
+            text: Some(text),
+            ..Default::default()
+        };
+        let (role, parts) = match message.message_type {
+            MessageType::SystemMessage => {
+                system.push(text(content));
+                continue;
+            }
+            MessageType::HumanMessage => {
+                let mut parts = vec![];
+                if !content.is_empty() {
+                    parts.push(text(content));
+                }
+                for image in message.images.iter().flatten() {
+                    parts.push(GeminiPart {
+                        inline_data: Some(to_inline_data(&image.image_url)?),
+                        ..Default::default()
+                    });
+                }
+                ("user", parts)
+            }
+            MessageType::AIMessage => {
+                let mut parts = vec![];
+                if !content.is_empty() {
+                    parts.push(text(content));
+                }
+                let tool_calls: Vec<ChatCompletionMessageToolCall> = match &message.tool_calls {
+                    Some(tool_calls) => serde_json::from_value(tool_calls.clone())?,
+                    None => vec![],
+                };
+                for tool_call in tool_calls {
+                    tool_names.insert(tool_call.id, tool_call.function.name.clone());
+                    parts.push(GeminiPart {
+                        function_call: Some(GeminiFunctionCall {
+                            name: tool_call.function.name,
+                            args: serde_json::from_str(&tool_call.function.arguments)?,
+                        }),
+                        ..Default::default()
+                    });
+                }
+                ("model", parts)
+            }
+            MessageType::ToolMessage => {
+                let name = message
+                    .name
+                    .clone()
+                    .or_else(|| tool_names.get(message.id.as_ref()?).cloned())
+                    .unwrap_or_default();
+                let part = GeminiPart {
+                    function_response: Some(GeminiFunctionResponse {
+                        name,
+                        response: json!({ "result": content }),
+                    }),
+                    ..Default::default()
+                };
+                ("user", vec![part])
+            }
+        };
+        // 連続する同じ役割のメッセージ (例: 複数のツール結果) は 1 つにまとめる
+        match contents.last_mut() {
+            Some(last) if last.role.as_deref() == Some(role) => last.parts.extend(parts),
+            _ => contents.push(GeminiContent {
+                role: Some(role.to_string()),
+                parts,
+            }),
+        }
+    }
+    let system_instruction = (!system.is_empty()).then_some(GeminiContent {
+        role: None,
+        parts: system,
+    });
+    Ok((system_instruction, contents))
+}
+
+/// Only data URLs are supported, as the native API does not fetch images.
+fn to_inline_data(image_url: &str) -> Result<GeminiInlineData, LLMError> {
+    image_url
+        .strip_prefix("data:")
+        .and_then(|data_url| data_url.split_once(";base64,"))
+        .map(|(mime_type, data)| GeminiInlineData {
+            mime_type: mime_type.to_string(),
+            data: data.to_string(),
+        })
+        .ok_or_else(|| {
+            LLMError::OtherError(format!(
+                "Gemini native API takes images as base64 data URLs: {}",
+                image_url
+            ))
+        })
+}
+
+/// What is known of the response so far. The chunks of a stream are merged into it.
+#[derive(Default)]
+struct Response {
+    function_calls: Vec<GeminiFunctionCall>,
+    tokens: Option<TokenUsage>,
+    metadata: GeminiMetadata,
+}
+
+impl Response {
+    /// Takes the function calls and the latest metadata of `response`, and returns its text.
+    fn update(&mut self, response: GeminiNativeResponse) -> String {
+        if let Some(usage) = response.usage_metadata {
+            self.tokens = Some(TokenUsage {
+                prompt_tokens: usage.prompt_token_count,
+                completion_tokens: usage.candidates_token_count,
+                total_tokens: usage.total_token_count,
+            });
+            self.metadata.cached_content_token_count = usage.cached_content_token_count;
+        }
+        if response.prompt_feedback.is_some() {
+            self.metadata.prompt_feedback = response.prompt_feedback;
+        }
+        if response.model_version.is_some() {
+            self.metadata.model_version = response.model_version;
+        }
+        let mut text = String::new();
+        let Some(candidate) = response.candidates.into_iter().next() else {
+            return text;
+        };
+        if candidate.finish_reason.is_some() {
+            self.metadata.finish_reason = candidate.finish_reason;
+        }
+        if !candidate.safety_ratings.is_empty() {
+            self.metadata.safety_ratings = candidate.safety_ratings;
+        }
+        if candidate.grounding_metadata.is_some() {
+            self.metadata.grounding_metadata = candidate.grounding_metadata;
+        }
+        for part in candidate
+            .content
+            .map(|content| content.parts)
+            .unwrap_or_default()
+        {
+            if let Some(t) = part.text {
+                text.push_str(&t);
+            }
+            if let Some(function_call) = part.function_call {
+                self.function_calls.push(function_call);
+            }
+        }
+        text
+    }
+
+    /// `id`, `name` and `arguments` are the ones of the first call,
+    /// and the AI message keeps all calls in the OpenAI format for a `ToolNode`.
+    /// The native API does not send ids, so unique ones are made up with `new_tool_call_id`.
+    fn into_result(self, text: String) -> Result<LLMResult, LLMError> {
+        let metadata = serde_json::to_value(&self.metadata)?;
+        let Some(first) = self.function_calls.first().cloned() else {
+            let result = GenerateResult::new(text, self.tokens).with_metadata(metadata);
+            return Ok(LLMResult::Generate(result));
+        };
+        let tool_calls: Vec<ChatCompletionMessageToolCall> = self
+            .function_calls
+            .into_iter()
+            .map(|call| ChatCompletionMessageToolCall {
+                id: new_tool_call_id(),
+                kind: ChatCompletionToolType::Function,
+                function: FunctionCall {
+                    name: call.name,
+                    arguments: call.args.to_string(),
+                },
+            })
+            .collect();
+        Ok(LLMResult::ToolCall(ToolCallResult {
+            id: tool_calls[0].id.clone(),
+            name: first.name,
+            arguments: first.args,
+            ai_message: Message {
+                content: (!text.is_empty()).then_some(text),
+                message_type: MessageType::AIMessage,
+                tool_calls: Some(serde_json::to_value(&tool_calls)?),
+                ..Default::default()
+            },
+            tokens: self.tokens,
+            metadata: Some(metadata),
+        }))
+    }
+}
+
+type ResponseStream = Pin<Box<dyn Stream<Item = Result<GeminiNativeResponse, LLMError>> + Send>>;
+
+struct StreamState {
+    responses: ResponseStream,
+    response: Response,
+    finished: bool,
+}
+
+/// The stream is polled by the caller, so that `emit_token` reaches the token sink of the caller's task.
+fn chat_stream(responses: ResponseStream) -> ChatStream {
+    let state = StreamState {
+        responses,
+        response: Response::default(),
+        finished: false,
+    };
+    Box::pin(stream::unfold(state, |mut state| async move {
+        while !state.finished {
+            let chunk = match state.responses.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => return Some((Err(e), state)),
+                None => {
+                    state.finished = true;
+                    let result = std::mem::take(&mut state.response).into_result(String::new());
+                    return Some((result, state));
+                }
+            };
+            let text = state.response.update(chunk);
+            if !text.is_empty() {
+                emit_token(&text);
+                let result = LLMResult::Generate(GenerateResult::new(text, None));
+                return Some((Ok(result), state));
+            }
+        }
+        None
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use anyhow::Result;
+    use futures::StreamExt;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        llm::{
+            LLM, MessagesBuilder, TokenSink,
+            gemini::{Gemini, GeminiBackend, GeminiConfigBuilder, GeminiModel},
+            with_token_sink,
+        },
+        types::openai::{FunctionDescription, Parameters, Tool, ToolType},
+    };
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    fn mock_gemini_api(method: &str, content_type: &str, body: &str) -> MockServer {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path(format!("/models/gemini-2.0-flash-001:{}", method))
+                .header("x-goog-api-key", "test_api_key");
+            then.status(200)
+                .header("content-type", content_type)
+                .body(body);
+        });
+        server
+    }
+
+    fn build_gemini(api_base: &str) -> Result<Gemini> {
+        let config = GeminiConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_api_base(api_base)
+            .with_model(GeminiModel::Gemini20)
+            .with_backend(GeminiBackend::Native)
+            .build()?;
+        Ok(Gemini::new(config))
+    }
+
+    fn hello() -> Messages {
+        MessagesBuilder::new().add_human_message("Hello").build()
+    }
+
+    #[test]
+    fn test_build_request() {
+        let config = GeminiConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_backend(GeminiBackend::Native)
+            .with_safety_setting("HARM_CATEGORY_HARASSMENT", "BLOCK_ONLY_HIGH")
+            .with_cached_content("cachedContents/abc123")
+            .with_google_search()
+            .build()
+            .unwrap();
+        let tools = vec![Tool {
+            r#type: ToolType::Function,
+            function: FunctionDescription {
+                name: "get_weather".to_string(),
+                description: "Get the current weather in a given location".to_string(),
+                parameters: Parameters {
+                    r#type: "object".to_string(),
+                    properties: HashMap::new(),
+                    required: vec![],
+                },
+            },
+        }];
+        let tool_calls = json!([
+            { "id": "call_0", "type": "function", "function": { "name": "get_weather", "arguments": "{\"location\":\"tokyo\"}" } },
+        ]);
+        let mut messages = MessagesBuilder::new()
+            .add_system_message("You are a weather assistant.")
+            .add_human_message("Weather here?")
+            .add_tools(tools)
+            .build();
+        messages.add_message(Message::new_human_message_with_images(vec![
+            "data:image/png;base64,iVBORw0KGgo=",
+        ]));
+        messages.add_message(Message::new_ai_message("").with_tool_calls(tool_calls));
+        messages.add_message(Message::new_tool_message("sunny", "call_0"));
+
+        let request = build_request(&config, &CallOptions::default(), &messages).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "contents": [
+                    { "role": "user", "parts": [
+                        { "text": "Weather here?" },
+                        { "inlineData": { "mimeType": "image/png", "data": "iVBORw0KGgo=" } },
+                    ] },
+                    { "role": "model", "parts": [
+                        { "functionCall": { "name": "get_weather", "args": { "location": "tokyo" } } },
+                    ] },
+                    { "role": "user", "parts": [
+                        { "functionResponse": { "name": "get_weather", "response": { "result": "sunny" } } },
+                    ] },
+                ],
+                "systemInstruction": { "parts": [{ "text": "You are a weather assistant." }] },
+                "tools": [
+                    { "functionDeclarations": [{
+                        "name": "get_weather",
+                        "description": "Get the current weather in a given location",
+                        "parameters": { "type": "object", "properties": {}, "required": [] },
+                    }] },
+                    { "googleSearch": {} },
+                ],
+                "safetySettings": [{ "category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH" }],
+                "cachedContent": "cachedContents/abc123",
+            })
+        );
+
+        let mut messages = Messages::default();
+        messages.add_message(Message::new_human_message_with_images(vec![
+            "https://example.com/cat.png",
+        ]));
+        assert!(build_request(&config, &CallOptions::default(), &messages).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invoke() -> Result<()> {
+        init_logger();
+        let body = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Hi there"}]},"finishReason":"STOP","safetyRatings":[{"category":"HARM_CATEGORY_HARASSMENT","probability":"NEGLIGIBLE"}],"groundingMetadata":{"webSearchQueries":["hello"]}}],"usageMetadata":{"promptTokenCount":5,"candidatesTokenCount":2,"totalTokenCount":7,"cachedContentTokenCount":3},"modelVersion":"gemini-2.0-flash-001"}"#;
+        let server = mock_gemini_api("generateContent", "application/json", body);
+        let gemini = build_gemini(&server.url(""))?;
+
+        let result = gemini.invoke(&hello()).await?;
+        let metadata = GeminiMetadata::from_result(&result).unwrap();
+        assert_eq!(metadata.finish_reason.as_deref(), Some("STOP"));
+        assert_eq!(metadata.safety_ratings[0].probability, "NEGLIGIBLE");
+        assert_eq!(
+            metadata.grounding_metadata,
+            Some(json!({ "webSearchQueries": ["hello"] }))
+        );
+        assert_eq!(metadata.cached_content_token_count, Some(3));
+        match result {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "Hi there");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_blocked_prompt() -> Result<()> {
+        init_logger();
+        let body = r#"{"promptFeedback":{"blockReason":"SAFETY","safetyRatings":[{"category":"HARM_CATEGORY_DANGEROUS_CONTENT","probability":"HIGH","blocked":true}]},"usageMetadata":{"promptTokenCount":5,"totalTokenCount":5}}"#;
+        let server = mock_gemini_api("generateContent", "application/json", body);
+        let gemini = build_gemini(&server.url(""))?;
+
+        // ブロックされた場合も結果を返し、理由はメタデータで分かる
+        let result = gemini.invoke(&hello()).await?;
+        let feedback = GeminiMetadata::from_result(&result)
+            .unwrap()
+            .prompt_feedback
+            .unwrap();
+        assert_eq!(feedback.block_reason.as_deref(), Some("SAFETY"));
+        assert!(feedback.safety_ratings[0].blocked);
+        match result {
+            LLMResult::Generate(result) => assert_eq!(result.generation(), ""),
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_with_options() -> Result<()> {
+        init_logger();
+        let body = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"Hi"}]},"finishReason":"STOP"}]}"#;
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/models/gemini-2.0-flash-001:generateContent")
+                .json_body_includes(
+                    r#"{"generationConfig":{"temperature":0.5,"maxOutputTokens":256}}"#,
+                );
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        });
+        let mut gemini = build_gemini(&server.url(""))?;
+        gemini.add_options(
+            &CallOptions::new()
+                .with_temperature(0.5)
+                .with_max_tokens(256),
+        );
+
+        gemini.invoke(&hello()).await?;
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_function_call() -> Result<()> {
+        init_logger();
+        let body = r#"{"candidates":[{"content":{"role":"model","parts":[{"functionCall":{"name":"get_weather","args":{"location":"tokyo"}}},{"functionCall":{"name":"get_weather","args":{"location":"osaka"}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":20,"candidatesTokenCount":10,"totalTokenCount":30}}"#;
+        let server = mock_gemini_api("generateContent", "application/json", body);
+        let gemini = build_gemini(&server.url(""))?;
+
+        let mut ids = vec![];
+        match gemini.invoke(&hello()).await? {
+            LLMResult::ToolCall(result) => {
+                assert!(result.id.starts_with("call_"));
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                // ToolNode が読めるように OpenAI 形式で保存する
+                let tool_calls: Vec<ChatCompletionMessageToolCall> =
+                    serde_json::from_value(result.ai_message.tool_calls.unwrap())?;
+                assert_eq!(tool_calls[0].id, result.id);
+                assert_eq!(tool_calls[1].function.arguments, r#"{"location":"osaka"}"#);
+                assert_eq!(result.tokens.unwrap().total_tokens, 30);
+                ids.extend(tool_calls.into_iter().map(|call| call.id));
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        // 次の応答の呼び出しとも id が重ならない
+        match gemini.invoke(&hello()).await? {
+            LLMResult::ToolCall(result) => ids.push(result.id),
+            _ => panic!("Expected ToolCall result"),
+        }
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream() -> Result<()> {
+        init_logger();
+        let body = r#"data: {"candidates":[{"content":{"role":"model","parts":[{"text":"hello"}]}}],"usageMetadata":{"promptTokenCount":5,"totalTokenCount":5}}
+
+data: {"candidates":[{"content":{"role":"model","parts":[{"text":" world"}]},"finishReason":"STOP","safetyRatings":[{"category":"HARM_CATEGORY_HARASSMENT","probability":"NEGLIGIBLE"}]}],"usageMetadata":{"promptTokenCount":5,"candidatesTokenCount":2,"totalTokenCount":7}}
+
+"#;
+        let server = mock_gemini_api("streamGenerateContent", "text/event-stream", body);
+        let gemini = build_gemini(&server.url(""))?;
+
+        let tokens = Arc::new(Mutex::new(String::new()));
+        let sink_tokens = tokens.clone();
+        let sink: TokenSink = Arc::new(move |token| sink_tokens.lock().unwrap().push_str(token));
+        let results = with_token_sink(sink, async {
+            let mut results = vec![];
+            let mut stream = gemini.invoke_stream(&hello()).await?;
+            while let Some(result) = stream.next().await {
+                match result? {
+                    LLMResult::Generate(result) => results.push(result),
+                    _ => panic!("Expected Generate result"),
+                }
+            }
+            Ok::<_, LLMError>(results)
+        })
+        .await?;
+
+        let generations: Vec<_> = results.iter().map(|result| result.generation()).collect();
+        assert_eq!(generations, vec!["hello", " world", ""]);
+        assert_eq!(*tokens.lock().unwrap(), "hello world");
+
+        let result = gemini.invoke_stream_one_result(&hello()).await?;
+        let metadata = GeminiMetadata::from_result(&result).unwrap();
+        assert_eq!(metadata.finish_reason.as_deref(), Some("STOP"));
+        assert_eq!(metadata.safety_ratings.len(), 1);
+        match result {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "hello world");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_function_call() -> Result<()> {
+        init_logger();
+        let body = r#"data: {"candidates":[{"content":{"role":"model","parts":[{"functionCall":{"name":"get_weather","args":{"location":"tokyo"}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":20,"candidatesTokenCount":10,"totalTokenCount":30}}
+
+"#;
+        let server = mock_gemini_api("streamGenerateContent", "text/event-stream", body);
+        let gemini = build_gemini(&server.url(""))?;
+
+        match gemini.invoke_stream_one_result(&hello()).await? {
+            LLMResult::ToolCall(result) => {
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(result.tokens.unwrap().total_tokens, 30);
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        Ok(())
+    }
+}
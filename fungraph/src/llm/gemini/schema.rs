@@ -1,6 +1,9 @@
+pub use crate::types::openai::OpenAIContent;
 use crate::{
-    llm::Message,
-    types::openai::{CompletionTokensDetails, FinishReason, PromptTokensDetails, Tool},
+    llm::{CallOptions, LLMResult},
+    types::openai::{
+        CompletionTokensDetails, FinishReason, FunctionDescription, PromptTokensDetails, Tool,
+    },
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -99,31 +102,38 @@ pub struct CompletionUsage {
     pub completion_tokens_details: Option<CompletionTokensDetails>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct OpenAIContent {
-    pub role: String,
-    pub content: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tool_calls: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tool_call_id: Option<String>,
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiInlineData {
+    pub mime_type: String,
+    /// Base64 encoded bytes.
+    pub data: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GeminiInlineData {
-    mime_type: String,
-    data: String,
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSafetyRating {
+    pub category: String,
+    pub probability: String,
+    #[serde(default)]
+    pub blocked: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GeminiSafetyRating {
-    category: String,
-    probability: String,
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPromptFeedback {
+    /// Set when the prompt was blocked, e.g. `SAFETY`. There are no candidates then.
+    pub block_reason: Option<String>,
+    #[serde(default)]
+    pub safety_ratings: Vec<GeminiSafetyRating>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GeminiPromptFeedback {
-    safety_ratings: Vec<GeminiSafetyRating>,
+/// Blocks content of `category` (e.g. `HARM_CATEGORY_HARASSMENT`) from `threshold` (e.g. `BLOCK_ONLY_HIGH`) on.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSafetySetting {
+    pub category: String,
+    pub threshold: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -136,4 +146,137 @@ pub struct GeminiRequest {
     pub model: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    #[serde(flatten)]
+    pub options: CallOptions,
+}
+
+// 以下は generateContent (ネイティブ API) 用
+// https://ai.google.dev/api/generate-content
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GeminiFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GeminiFunctionResponse {
+    pub name: String,
+    pub response: Value,
+}
+
+/// One of the fields is set.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPart {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<GeminiInlineData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<GeminiFunctionCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_response: Option<GeminiFunctionResponse>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct GeminiContent {
+    /// `user` or `model`. Not set for `systemInstruction`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTool {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_declarations: Option<Vec<FunctionDescription>>,
+    /// Grounding with Google Search. Always an empty object.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_search: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiNativeRequest {
+    pub contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiContent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<GeminiTool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub safety_settings: Vec<GeminiSafetySetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GeminiGenerationConfig>,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    /// Missing when the candidate was blocked.
+    pub content: Option<GeminiContent>,
+    pub finish_reason: Option<String>,
+    #[serde(default)]
+    pub safety_ratings: Vec<GeminiSafetyRating>,
+    pub grounding_metadata: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiUsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    #[serde(default)]
+    pub total_token_count: u32,
+    pub cached_content_token_count: Option<u32>,
+}
+
+/// Response of `generateContent`, or one chunk of `streamGenerateContent`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiNativeResponse {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    pub prompt_feedback: Option<GeminiPromptFeedback>,
+    pub usage_metadata: Option<GeminiUsageMetadata>,
+    pub model_version: Option<String>,
+}
+
+/// Fields of a native `generateContent` response that have no place in `LLMResult`.
+/// They are kept in the metadata of the result, see `GeminiMetadata::from_result`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct GeminiMetadata {
+    pub finish_reason: Option<String>,
+    pub safety_ratings: Vec<GeminiSafetyRating>,
+    pub prompt_feedback: Option<GeminiPromptFeedback>,
+    pub grounding_metadata: Option<Value>,
+    pub cached_content_token_count: Option<u32>,
+    pub model_version: Option<String>,
+}
+
+impl GeminiMetadata {
+    /// `None` unless the result comes from `GeminiBackend::Native`.
+    pub fn from_result(result: &LLMResult) -> Option<Self> {
+        serde_json::from_value(result.metadata()?.clone()).ok()
+    }
 }
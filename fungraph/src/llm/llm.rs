@@ -1,13 +1,22 @@
 use async_trait::async_trait;
+use futures::Stream;
 use log::debug;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
 
 use crate::types::TokenUsage;
 
-use super::{LLMError, Message, Messages, gemini::ChatStream};
+use super::{LLMError, Message, Messages};
+
+/// Stream of partial results returned by `LLM::invoke_stream`.
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<LLMResult, LLMError>> + Send>>;
 
 #[async_trait]
 pub trait LLM: Send + Sync {
@@ -18,19 +27,59 @@ pub trait LLM: Send + Sync {
     fn add_options(&mut self, options: &CallOptions);
 }
 
-#[derive(Clone, Debug)]
-pub struct CallOptions {}
+/// Sampling options sent with every request of an `LLM`.
+/// Unset options are left out of the request, so the provider defaults apply.
+///
+/// # Usage
+/// ```rust,ignore
+/// let llm = OpenAI::new(config).with_options(CallOptions::new().with_temperature(0.2));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct CallOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
 
 impl CallOptions {
-    pub fn merge(&self, other: &CallOptions) -> CallOptions {
-        debug!("Merging options: {:?} and {:?}", self, other);
-        CallOptions {}
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
     }
-}
 
-impl Default for CallOptions {
-    fn default() -> Self {
-        Self {}
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Returns `self` with the options set in `other` overriding its own.
+    pub fn merge(&self, other: &CallOptions) -> CallOptions {
+        debug!("Merging options: {:?} and {:?}", self, other);
+        CallOptions {
+            temperature: other.temperature.or(self.temperature),
+            max_tokens: other.max_tokens.or(self.max_tokens),
+            top_p: other.top_p.or(self.top_p),
+            stop: other.stop.clone().or_else(|| self.stop.clone()),
+        }
     }
 }
 
@@ -40,11 +89,39 @@ pub enum LLMResult {
     ToolCall(ToolCallResult),
 }
 
+impl LLMResult {
+    /// The AI message to append to the chat history, including its tool calls.
+    pub fn ai_message(&self) -> Message {
+        match self {
+            LLMResult::Generate(result) => Message::new_ai_message(result.generation()),
+            LLMResult::ToolCall(result) => result.ai_message.clone(),
+        }
+    }
+
+    /// Token usage reported by the provider, if any.
+    pub fn tokens(&self) -> Option<&TokenUsage> {
+        match self {
+            LLMResult::Generate(result) => result.tokens(),
+            LLMResult::ToolCall(result) => result.tokens.as_ref(),
+        }
+    }
+
+    /// Provider specific fields of the response, e.g. `GeminiMetadata`.
+    pub fn metadata(&self) -> Option<&Value> {
+        match self {
+            LLMResult::Generate(result) => result.metadata(),
+            LLMResult::ToolCall(result) => result.metadata.as_ref(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GenerateResult {
     tokens: Option<TokenUsage>,
     generation: String,
     tool_call: Option<String>,
+    #[serde(default)]
+    metadata: Option<Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -53,6 +130,26 @@ pub struct ToolCallResult {
     pub name: String,
     pub arguments: Value,
     pub ai_message: Message,
+    #[serde(default)]
+    pub tokens: Option<TokenUsage>,
+    /// Provider specific fields of the response, see `LLMResult::metadata`.
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+/// Makes up the id of a tool call for providers that do not send one, e.g. `call_18c2f3a4b5d6e7f8_0`.
+/// The time prefix keeps the ids unique across restarts, e.g. in a history restored from a checkpoint.
+pub(crate) fn new_tool_call_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!(
+        "call_{:x}_{}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
 }
 
 impl GenerateResult {
@@ -61,9 +158,15 @@ impl GenerateResult {
             generation,
             tokens,
             tool_call: None,
+            metadata: None,
         }
     }
 
+    pub fn with_metadata(mut self, metadata: Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
     pub fn to_hashmap(&self) -> HashMap<String, String> {
         let mut map = HashMap::new();
 
@@ -86,6 +189,14 @@ impl GenerateResult {
         map
     }
 
+    pub fn tokens(&self) -> Option<&TokenUsage> {
+        self.tokens.as_ref()
+    }
+
+    pub fn metadata(&self) -> Option<&Value> {
+        self.metadata.as_ref()
+    }
+
     pub fn generation(&self) -> &str {
         &self.generation
     }
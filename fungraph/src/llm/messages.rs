@@ -13,9 +13,10 @@ use crate::types::openai::Tool;
 /// let ai_message_type = MessageType::AIMessage;
 /// let human_message_type = MessageType::HumanMessage;
 /// ```
-#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone)]
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Default)]
 pub enum MessageType {
     #[serde(rename = "system")]
+    #[default]
     SystemMessage,
     #[serde(rename = "ai")]
     AIMessage,
@@ -25,13 +26,8 @@ pub enum MessageType {
     ToolMessage,
 }
 
-impl Default for MessageType {
-    fn default() -> Self {
-        Self::SystemMessage
-    }
-}
-
 impl MessageType {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         match self {
             MessageType::SystemMessage => "system".to_owned(),
@@ -170,6 +166,12 @@ pub struct MessagesBuilder {
     tools: Vec<Tool>,
 }
 
+impl Default for MessagesBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MessagesBuilder {
     pub fn new() -> Self {
         Self {
@@ -1,8 +1,17 @@
+pub mod anthropic;
+pub mod fake;
 pub mod gemini;
+#[allow(clippy::module_inception)]
 mod llm;
+pub mod ollama;
+pub mod openai;
+pub(crate) mod sse;
 pub use llm::*;
 pub mod messages;
 pub use messages::*;
 
 pub mod error;
 pub use error::*;
+
+pub mod token;
+pub use token::*;
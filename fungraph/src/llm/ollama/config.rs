@@ -0,0 +1,81 @@
+use anyhow::Result;
+
+#[derive(Clone)]
+pub struct OllamaConfig {
+    api_base: String,
+    model: String,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            api_base: "http://localhost:11434".to_string(),
+            model: "llama3.2".to_string(),
+        }
+    }
+}
+
+impl OllamaConfig {
+    pub fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+pub struct OllamaConfigBuilder {
+    config: OllamaConfig,
+}
+
+impl Default for OllamaConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OllamaConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: OllamaConfig::default(),
+        }
+    }
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.config.api_base = api_base.into();
+        self
+    }
+    /// Name of a pulled model, e.g. `qwen2.5:7b`.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.config.model = model.into();
+        self
+    }
+    pub fn build(self) -> Result<OllamaConfig> {
+        if self.config.model.is_empty() {
+            anyhow::bail!("Model must be set");
+        }
+
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ollama_config_builder_default() {
+        // ローカルのサーバーは API キー無しで使える
+        let config = OllamaConfigBuilder::new().build().unwrap();
+        assert_eq!(config.api_base, "http://localhost:11434");
+        assert_eq!(config.model, "llama3.2");
+    }
+
+    #[test]
+    fn test_ollama_config_builder_model_empty() {
+        let result = OllamaConfigBuilder::new().with_model("").build();
+        match result {
+            Ok(_) => panic!("Model must be required"),
+            Err(err) => assert_eq!(err.to_string(), "Model must be set"),
+        }
+    }
+}
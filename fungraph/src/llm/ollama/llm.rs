@@ -0,0 +1,646 @@
+use std::{collections::HashMap, pin::Pin};
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt, stream};
+use log::debug;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::{
+    llm::{
+        CallOptions, ChatStream, GenerateResult, LLM, LLMError, LLMResult, Message, MessageType,
+        Messages, ToolCallResult, emit_token, new_tool_call_id,
+    },
+    types::{
+        TokenUsage,
+        openai::{ChatCompletionMessageToolCall, ChatCompletionToolType, FunctionCall},
+    },
+};
+
+use super::{
+    OllamaConfig, OllamaFunctionCall, OllamaMessage, OllamaOptions, OllamaRequest, OllamaResponse,
+    OllamaToolCall,
+};
+
+type ResponseStream = Pin<Box<dyn Stream<Item = Result<OllamaResponse, LLMError>> + Send>>;
+
+#[derive(Clone)]
+pub struct Ollama {
+    config: OllamaConfig,
+    options: CallOptions,
+}
+
+impl Ollama {
+    pub fn new(config: OllamaConfig) -> Self {
+        Self {
+            config,
+            options: CallOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn build_request(
+        &self,
+        messages: &Messages,
+        is_stream: bool,
+    ) -> Result<OllamaRequest, LLMError> {
+        Ok(OllamaRequest {
+            model: self.config.model().to_string(),
+            messages: to_ollama_messages(messages)?,
+            tools: (!messages.tools.is_empty()).then(|| messages.tools.clone()),
+            stream: is_stream,
+            options: to_ollama_options(&self.options),
+        })
+    }
+
+    /// Sends the request, and turns a non-success status into an error.
+    async fn post(&self, request: &OllamaRequest) -> Result<reqwest::Response, LLMError> {
+        let url = format!("{}/api/chat", self.config.api_base());
+        debug!(
+            "Ollama Request: {} {}",
+            url,
+            serde_json::to_string(request)?
+        );
+        let response = reqwest::Client::new()
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(request)?)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            return Err(LLMError::OtherError(format!(
+                "Ollama API error: {} - {}",
+                status, body
+            )));
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl LLM for Ollama {
+    async fn generate(&self, prompt: &Messages) -> Result<LLMResult, LLMError> {
+        let response = self.post(&self.build_request(prompt, false)?).await?;
+        let body = response.text().await?;
+        debug!("Ollama Response Body: {:?}", body);
+        let response: OllamaResponse = serde_json::from_str(&body)?;
+        if let Some(error) = response.error {
+            return Err(LLMError::OtherError(format!("Ollama API error: {}", error)));
+        }
+        let tokens = to_tokens(&response);
+        to_result(
+            response.message.content,
+            response.message.tool_calls.unwrap_or_default(),
+            tokens,
+        )
+    }
+
+    async fn invoke(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        self.generate(messages).await
+    }
+
+    async fn invoke_stream_one_result(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        let mut chunks = self.invoke_stream(messages).await?;
+        let mut generation = String::new();
+        let mut tokens = None;
+        while let Some(chunk) = chunks.next().await {
+            match chunk? {
+                LLMResult::Generate(chunk) => {
+                    generation.push_str(chunk.generation());
+                    if let Some(usage) = chunk.tokens() {
+                        tokens = Some(usage.clone());
+                    }
+                }
+                tool_call => return Ok(tool_call),
+            }
+        }
+        Ok(LLMResult::Generate(GenerateResult::new(generation, tokens)))
+    }
+
+    /// Yields a `LLMResult::Generate` per content line.
+    /// Tool calls are collected and yielded as one `LLMResult::ToolCall` at the end.
+    /// The token usage comes with the last result.
+    async fn invoke_stream(&self, messages: &Messages) -> Result<ChatStream, LLMError> {
+        let response = self.post(&self.build_request(messages, true)?).await?;
+        Ok(chat_stream(ndjson_stream(response)))
+    }
+
+    fn add_options(&mut self, options: &CallOptions) {
+        self.options = self.options.merge(options);
+    }
+}
+
+/// Tool calls are stored in the OpenAI format in `Message::tool_calls`,
+/// and their string arguments become JSON objects.
+/// Ollama has no tool call ids, so a tool message is matched to its call by `Message::id`
+/// to fill in `tool_name`.
+fn to_ollama_messages(messages: &Messages) -> Result<Vec<OllamaMessage>, LLMError> {
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    let mut ollama_messages = vec![];
+    for message in messages.messages.iter() {
+        let mut ollama_message = OllamaMessage {
+            content: message.content.clone().unwrap_or_default(),
+            images: message.images.as_ref().map(|images| {
+                images
+                    .iter()
+                    .map(|image| to_base64_image(&image.image_url))
+                    .collect()
+            }),
+            ..Default::default()
+        };
+        match message.message_type {
+            MessageType::SystemMessage => ollama_message.role = "system".to_string(),
+            MessageType::HumanMessage => ollama_message.role = "user".to_string(),
+            MessageType::AIMessage => {
+                ollama_message.role = "assistant".to_string();
+                let tool_calls: Vec<ChatCompletionMessageToolCall> = match &message.tool_calls {
+                    Some(tool_calls) => serde_json::from_value(tool_calls.clone())?,
+                    None => vec![],
+                };
+                let mut calls = vec![];
+                for tool_call in tool_calls {
+                    tool_names.insert(tool_call.id, tool_call.function.name.clone());
+                    calls.push(OllamaToolCall {
+                        function: OllamaFunctionCall {
+                            name: tool_call.function.name,
+                            arguments: serde_json::from_str(&tool_call.function.arguments)?,
+                        },
+                    });
+                }
+                ollama_message.tool_calls = (!calls.is_empty()).then_some(calls);
+            }
+            MessageType::ToolMessage => {
+                ollama_message.role = "tool".to_string();
+                ollama_message.tool_name = message.name.clone().or_else(|| {
+                    let id = message.id.as_ref()?;
+                    tool_names.get(id).cloned()
+                });
+            }
+        }
+        ollama_messages.push(ollama_message);
+    }
+    Ok(ollama_messages)
+}
+
+/// Ollama takes raw base64 data, so the prefix of a data URL is dropped.
+fn to_base64_image(image_url: &str) -> String {
+    match image_url.strip_prefix("data:") {
+        Some(data_url) => data_url
+            .split_once(',')
+            .map_or(data_url, |(_, data)| data)
+            .to_string(),
+        None => image_url.to_string(),
+    }
+}
+
+/// Returns `None` when no option is set, so the model defaults apply.
+fn to_ollama_options(options: &CallOptions) -> Option<OllamaOptions> {
+    let options = OllamaOptions {
+        temperature: options.temperature,
+        num_predict: options.max_tokens,
+        top_p: options.top_p,
+        stop: options.stop.clone(),
+    };
+    (options != OllamaOptions::default()).then_some(options)
+}
+
+/// The counts are only sent with the last response.
+fn to_tokens(response: &OllamaResponse) -> Option<TokenUsage> {
+    response.done.then(|| {
+        TokenUsage::new(
+            response.prompt_eval_count.unwrap_or_default(),
+            response.eval_count.unwrap_or_default(),
+        )
+    })
+}
+
+/// `id`, `name` and `arguments` are the ones of the first call,
+/// and the AI message keeps all calls in the OpenAI format for a `ToolNode`.
+/// Ollama does not send ids, so unique ones are made up with `new_tool_call_id`.
+fn to_result(
+    content: String,
+    tool_calls: Vec<OllamaToolCall>,
+    tokens: Option<TokenUsage>,
+) -> Result<LLMResult, LLMError> {
+    let Some(first) = tool_calls.first().cloned() else {
+        return Ok(LLMResult::Generate(GenerateResult::new(content, tokens)));
+    };
+    let tool_calls: Vec<ChatCompletionMessageToolCall> = tool_calls
+        .into_iter()
+        .map(|call| ChatCompletionMessageToolCall {
+            id: new_tool_call_id(),
+            kind: ChatCompletionToolType::Function,
+            function: FunctionCall {
+                name: call.function.name,
+                arguments: call.function.arguments.to_string(),
+            },
+        })
+        .collect();
+    Ok(LLMResult::ToolCall(ToolCallResult {
+        id: tool_calls[0].id.clone(),
+        name: first.function.name,
+        arguments: first.function.arguments,
+        ai_message: Message {
+            content: (!content.is_empty()).then_some(content),
+            message_type: MessageType::AIMessage,
+            tool_calls: Some(serde_json::to_value(&tool_calls)?),
+            ..Default::default()
+        },
+        tokens,
+        metadata: None,
+    }))
+}
+
+struct LineState {
+    bytes: Pin<Box<dyn Stream<Item = reqwest::Result<Vec<u8>>> + Send>>,
+    buffer: Vec<u8>,
+    ended: bool,
+}
+
+/// Parses each line of a newline delimited JSON response as `OllamaResponse`.
+/// A line may be split over several chunks of the body, so the bytes are buffered until a newline.
+fn ndjson_stream(response: reqwest::Response) -> ResponseStream {
+    let state = LineState {
+        bytes: Box::pin(
+            response
+                .bytes_stream()
+                .map(|chunk| chunk.map(|bytes| bytes.to_vec())),
+        ),
+        buffer: vec![],
+        ended: false,
+    };
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            let line = match state.buffer.iter().position(|b| *b == b'\n') {
+                Some(pos) => state.buffer.drain(..=pos).collect::<Vec<u8>>(),
+                None if state.ended => std::mem::take(&mut state.buffer),
+                None => {
+                    match state.bytes.next().await {
+                        Some(Ok(chunk)) => state.buffer.extend_from_slice(&chunk),
+                        Some(Err(e)) => return Some((Err(e.into()), state)),
+                        None => state.ended = true,
+                    }
+                    continue;
+                }
+            };
+            if line.iter().all(u8::is_ascii_whitespace) {
+                if state.ended && state.buffer.is_empty() {
+                    return None;
+                }
+                continue;
+            }
+            let response = serde_json::from_slice(&line).map_err(LLMError::from);
+            return Some((response, state));
+        }
+    }))
+}
+
+struct StreamState {
+    responses: ResponseStream,
+    tool_calls: Vec<OllamaToolCall>,
+    tokens: Option<TokenUsage>,
+    done: bool,
+    finished: bool,
+}
+
+impl StreamState {
+    /// The result yielded after the last response.
+    fn finish(&mut self) -> Result<LLMResult, LLMError> {
+        self.finished = true;
+        to_result(
+            String::new(),
+            std::mem::take(&mut self.tool_calls),
+            self.tokens.take(),
+        )
+    }
+}
+
+/// The stream is polled by the caller, so that `emit_token` reaches the token sink of the caller's task.
+fn chat_stream(responses: ResponseStream) -> ChatStream {
+    let state = StreamState {
+        responses,
+        tool_calls: vec![],
+        tokens: None,
+        done: false,
+        finished: false,
+    };
+    Box::pin(stream::unfold(state, |mut state| async move {
+        while !state.finished {
+            if state.done {
+                let result = state.finish();
+                return Some((result, state));
+            }
+            let response = match state.responses.next().await {
+                Some(Ok(response)) => response,
+                Some(Err(e)) => return Some((Err(e), state)),
+                None => {
+                    state.done = true;
+                    continue;
+                }
+            };
+            if let Some(error) = response.error {
+                state.finished = true;
+                let error = LLMError::OtherError(format!("Ollama API error: {}", error));
+                return Some((Err(error), state));
+            }
+            if response.done {
+                state.done = true;
+                state.tokens = to_tokens(&response);
+            }
+            // ツール呼び出しは 1 行にまとめて送られてくる
+            if let Some(tool_calls) = response.message.tool_calls {
+                state.tool_calls.extend(tool_calls);
+            }
+            let content = response.message.content;
+            if !content.is_empty() {
+                emit_token(&content);
+                let result = LLMResult::Generate(GenerateResult::new(content, None));
+                return Some((Ok(result), state));
+            }
+        }
+        None
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use anyhow::Result;
+    use futures::StreamExt;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        llm::{MessagesBuilder, TokenSink, ollama::OllamaConfigBuilder, with_token_sink},
+        types::openai::{FunctionDescription, Parameters, Tool, ToolType},
+    };
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    fn mock_ollama_api(status: u16, content_type: &str, body: &str) -> MockServer {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/api/chat");
+            then.status(status)
+                .header("content-type", content_type)
+                .body(body);
+        });
+        server
+    }
+
+    fn build_ollama(server: &MockServer) -> Result<Ollama> {
+        let config = OllamaConfigBuilder::new()
+            .with_api_base(&server.url(""))
+            .with_model("qwen2.5:7b")
+            .build()?;
+        Ok(Ollama::new(config))
+    }
+
+    fn hello() -> Messages {
+        MessagesBuilder::new().add_human_message("Hello").build()
+    }
+
+    fn weather_tool() -> Tool {
+        Tool {
+            r#type: ToolType::Function,
+            function: FunctionDescription {
+                name: "get_weather".to_string(),
+                description: "Get the current weather in a given location".to_string(),
+                parameters: Parameters {
+                    r#type: "object".to_string(),
+                    properties: HashMap::new(),
+                    required: vec![],
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_build_request() {
+        let server = MockServer::start();
+        let ollama = build_ollama(&server).unwrap();
+        let tool_calls = json!([
+            { "id": "call_0", "type": "function", "function": { "name": "get_weather", "arguments": "{\"location\":\"tokyo\"}" } },
+        ]);
+        let mut messages = MessagesBuilder::new()
+            .add_system_message("You are a weather assistant.")
+            .add_tools(vec![weather_tool()])
+            .build();
+        messages.add_message(Message::new_human_message_with_images(vec![
+            "data:image/png;base64,iVBORw0KGgo=",
+        ]));
+        messages.add_message(Message::new_ai_message("").with_tool_calls(tool_calls));
+        messages.add_message(Message::new_tool_message("sunny", "call_0"));
+
+        let request = ollama.build_request(&messages, false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": "qwen2.5:7b",
+                "messages": [
+                    { "role": "system", "content": "You are a weather assistant." },
+                    { "role": "user", "content": "", "images": ["iVBORw0KGgo="] },
+                    { "role": "assistant", "content": "", "tool_calls": [
+                        { "function": { "name": "get_weather", "arguments": { "location": "tokyo" } } },
+                    ] },
+                    { "role": "tool", "content": "sunny", "tool_name": "get_weather" },
+                ],
+                "tools": [{
+                    "type": "function",
+                    "function": {
+                        "name": "get_weather",
+                        "description": "Get the current weather in a given location",
+                        "parameters": { "type": "object", "properties": {}, "required": [] },
+                    },
+                }],
+                "stream": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_request_with_options() {
+        let server = MockServer::start();
+        let mut ollama = build_ollama(&server).unwrap();
+        ollama.add_options(
+            &CallOptions::new()
+                .with_temperature(0.5)
+                .with_max_tokens(256),
+        );
+
+        let request = ollama.build_request(&hello(), false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["options"],
+            json!({ "temperature": 0.5, "num_predict": 256 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invoke() -> Result<()> {
+        init_logger();
+        let body = r#"{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":"Hi there"},"done_reason":"stop","done":true,"prompt_eval_count":5,"eval_count":2}"#;
+        let server = mock_ollama_api(200, "application/json", body);
+        let ollama = build_ollama(&server)?;
+
+        match ollama.invoke(&hello()).await? {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "Hi there");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_tool_calls() -> Result<()> {
+        init_logger();
+        let body = r#"{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"get_weather","arguments":{"location":"tokyo"}}},{"function":{"name":"get_weather","arguments":{"location":"osaka"}}}]},"done_reason":"stop","done":true,"prompt_eval_count":20,"eval_count":10}"#;
+        let server = mock_ollama_api(200, "application/json", body);
+        let ollama = build_ollama(&server)?;
+
+        let mut ids = vec![];
+        match ollama.invoke(&hello()).await? {
+            LLMResult::ToolCall(result) => {
+                assert!(result.id.starts_with("call_"));
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(result.ai_message.content, None);
+                // ToolNode が読めるように OpenAI 形式で保存する
+                let tool_calls: Vec<ChatCompletionMessageToolCall> =
+                    serde_json::from_value(result.ai_message.tool_calls.unwrap())?;
+                assert_eq!(tool_calls.len(), 2);
+                assert_eq!(tool_calls[0].id, result.id);
+                assert_eq!(tool_calls[1].function.arguments, r#"{"location":"osaka"}"#);
+                assert_eq!(result.tokens.unwrap().total_tokens, 30);
+                ids.extend(tool_calls.into_iter().map(|call| call.id));
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        // 次の応答の呼び出しとも id が重ならない
+        match ollama.invoke(&hello()).await? {
+            LLMResult::ToolCall(result) => ids.push(result.id),
+            _ => panic!("Expected ToolCall result"),
+        }
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_error() -> Result<()> {
+        init_logger();
+        let body = r#"{"error":"model \"qwen2.5:7b\" not found, try pulling it first"}"#;
+        let server = mock_ollama_api(404, "application/json", body);
+        let ollama = build_ollama(&server)?;
+        match ollama.invoke(&hello()).await {
+            Err(LLMError::OtherError(message)) => assert!(message.contains("404")),
+            _ => panic!("Expected OtherError"),
+        }
+        match ollama.invoke_stream(&hello()).await {
+            Err(LLMError::OtherError(message)) => assert!(message.contains("not found")),
+            _ => panic!("Expected OtherError"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream() -> Result<()> {
+        init_logger();
+        let body = r#"{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":"hello"},"done":false}
+{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":" world"},"done":false}
+{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":""},"done_reason":"stop","done":true,"prompt_eval_count":5,"eval_count":2}
+"#;
+        let server = mock_ollama_api(200, "application/x-ndjson", body);
+        let ollama = build_ollama(&server)?;
+
+        let tokens = Arc::new(Mutex::new(String::new()));
+        let sink_tokens = tokens.clone();
+        let sink: TokenSink = Arc::new(move |token| sink_tokens.lock().unwrap().push_str(token));
+        let results = with_token_sink(sink, async {
+            let mut results = vec![];
+            let mut stream = ollama.invoke_stream(&hello()).await?;
+            while let Some(result) = stream.next().await {
+                match result? {
+                    LLMResult::Generate(result) => results.push(result),
+                    _ => panic!("Expected Generate result"),
+                }
+            }
+            Ok::<_, LLMError>(results)
+        })
+        .await?;
+
+        let generations: Vec<_> = results.iter().map(|result| result.generation()).collect();
+        assert_eq!(generations, vec!["hello", " world", ""]);
+        assert_eq!(results[2].tokens().unwrap().total_tokens, 7);
+        assert_eq!(*tokens.lock().unwrap(), "hello world");
+
+        match ollama.invoke_stream_one_result(&hello()).await? {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "hello world");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_tool_calls() -> Result<()> {
+        init_logger();
+        // 最後の行に改行が無くても読めること
+        let body = r#"{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"get_weather","arguments":{"location":"tokyo"}}}]},"done":false}
+
+{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":""},"done_reason":"stop","done":true,"prompt_eval_count":20,"eval_count":10}"#;
+        let server = mock_ollama_api(200, "application/x-ndjson", body);
+        let ollama = build_ollama(&server)?;
+
+        match ollama.invoke_stream_one_result(&hello()).await? {
+            LLMResult::ToolCall(result) => {
+                assert!(result.id.starts_with("call_"));
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(result.tokens.unwrap().total_tokens, 30);
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_error_line() -> Result<()> {
+        init_logger();
+        let body = r#"{"model":"qwen2.5:7b","created_at":"2025-04-02T12:00:00Z","message":{"role":"assistant","content":"hel"},"done":false}
+{"error":"an error was encountered while running the model"}
+"#;
+        let server = mock_ollama_api(200, "application/x-ndjson", body);
+        let ollama = build_ollama(&server)?;
+
+        let mut stream = ollama.invoke_stream(&hello()).await?;
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(LLMResult::Generate(_)))
+        ));
+        match stream.next().await {
+            Some(Err(LLMError::OtherError(message))) => {
+                assert!(message.contains("an error was encountered"))
+            }
+            _ => panic!("Expected OtherError"),
+        }
+        assert!(stream.next().await.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+
+use anyhow::Result;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpenAIModel {
+    Gpt4o,
+    Gpt4oMini,
+    Gpt41,
+    Gpt41Mini,
+    O3Mini,
+    /// Any other model name, e.g. of an OpenAI compatible server.
+    Other(String),
+}
+
+impl fmt::Display for OpenAIModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenAIModel::Gpt4o => write!(f, "gpt-4o"),
+            OpenAIModel::Gpt4oMini => write!(f, "gpt-4o-mini"),
+            OpenAIModel::Gpt41 => write!(f, "gpt-4.1"),
+            OpenAIModel::Gpt41Mini => write!(f, "gpt-4.1-mini"),
+            OpenAIModel::O3Mini => write!(f, "o3-mini"),
+            OpenAIModel::Other(model) => write!(f, "{}", model),
+        }
+    }
+}
+
+impl From<OpenAIModel> for String {
+    fn from(val: OpenAIModel) -> Self {
+        val.to_string()
+    }
+}
+
+#[derive(Clone)]
+pub struct OpenAIConfig {
+    api_base: String,
+    api_key: String,
+    model: OpenAIModel,
+}
+
+impl Default for OpenAIConfig {
+    fn default() -> Self {
+        Self {
+            api_base: "https://api.openai.com/v1".to_string(),
+            api_key: "".to_string(),
+            model: OpenAIModel::Gpt4oMini,
+        }
+    }
+}
+
+impl OpenAIConfig {
+    pub fn api_base(&self) -> &str {
+        &self.api_base
+    }
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+    pub fn model(&self) -> &OpenAIModel {
+        &self.model
+    }
+}
+
+pub struct OpenAIConfigBuilder {
+    config: OpenAIConfig,
+}
+
+impl Default for OpenAIConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenAIConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: OpenAIConfig::default(),
+        }
+    }
+    pub fn with_api_base(mut self, api_base: &str) -> Self {
+        self.config.api_base = api_base.into();
+        self
+    }
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.config.api_key = api_key.into();
+        self
+    }
+    pub fn with_model(mut self, model: OpenAIModel) -> Self {
+        self.config.model = model;
+        self
+    }
+    pub fn build(self) -> Result<OpenAIConfig> {
+        if self.config.api_key.is_empty() {
+            anyhow::bail!("API key must be set");
+        }
+
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_config_builder_api_key_empty() {
+        let result = OpenAIConfigBuilder::new().build();
+        match result {
+            Ok(_) => panic!("API key must be required"),
+            Err(err) => assert_eq!(err.to_string(), "API key must be set"),
+        }
+    }
+
+    #[test]
+    fn test_openai_config_builder_all_fields() {
+        let config = OpenAIConfigBuilder::new()
+            .with_api_base("https://example.com")
+            .with_api_key("test_api_key")
+            .with_model(OpenAIModel::Other("my-model".to_string()))
+            .build()
+            .unwrap();
+        assert_eq!(config.api_base, "https://example.com");
+        assert_eq!(config.api_key, "test_api_key");
+        assert_eq!(config.model.to_string(), "my-model");
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use futures::{StreamExt, stream};
+use log::debug;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest_eventsource::RequestBuilderExt;
+
+use crate::{
+    llm::{
+        CallOptions, ChatStream, GenerateResult, LLM, LLMError, LLMResult, Message, MessageType,
+        Messages, ToolCallResult, emit_token, sse::stream,
+    },
+    types::{
+        TokenUsage,
+        openai::{
+            ChatCompletionMessageToolCall, ChatCompletionResponseStream, ChatCompletionToolType,
+            CompletionUsage, FunctionCall, OpenAIMessages, OpenAIResponse,
+        },
+    },
+};
+
+use super::{OpenAIConfig, OpenAIRequest, StreamOptions};
+
+#[derive(Clone)]
+pub struct OpenAI {
+    config: OpenAIConfig,
+    options: CallOptions,
+}
+
+impl OpenAI {
+    pub fn new(config: OpenAIConfig) -> Self {
+        Self {
+            config,
+            options: CallOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn build_request(&self, messages: &Messages, is_stream: bool) -> OpenAIRequest {
+        let has_tools = !messages.tools.is_empty();
+        OpenAIRequest {
+            messages: messages.to_openai_messages(),
+            tools: has_tools.then(|| messages.tools.clone()),
+            tool_choice: has_tools.then(|| "auto".to_string()),
+            model: self.config.model().clone().into(),
+            stream: is_stream.then_some(true),
+            stream_options: is_stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            options: self.options.clone(),
+        }
+    }
+
+    fn post(&self, request: &OpenAIRequest) -> Result<reqwest::RequestBuilder, LLMError> {
+        let url = format!("{}/chat/completions", self.config.api_base());
+        debug!(
+            "OpenAI Request: {} {}",
+            url,
+            serde_json::to_string(request)?
+        );
+        Ok(reqwest::Client::new()
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(AUTHORIZATION, format!("Bearer {}", self.config.api_key()))
+            .body(serde_json::to_string(request)?))
+    }
+}
+
+#[async_trait]
+impl LLM for OpenAI {
+    async fn generate(&self, prompt: &Messages) -> Result<LLMResult, LLMError> {
+        let response = self
+            .post(&self.build_request(prompt, false))?
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        debug!("OpenAI Response Body: {:?}", body);
+        if !status.is_success() {
+            return Err(LLMError::OtherError(format!(
+                "OpenAI API error: {} - {}",
+                status, body
+            )));
+        }
+        to_result(serde_json::from_str(&body)?)
+    }
+
+    async fn invoke(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        self.generate(messages).await
+    }
+
+    async fn invoke_stream_one_result(&self, messages: &Messages) -> Result<LLMResult, LLMError> {
+        let mut chunks = self.invoke_stream(messages).await?;
+        let mut generation = String::new();
+        let mut tokens = None;
+        while let Some(chunk) = chunks.next().await {
+            match chunk? {
+                LLMResult::Generate(chunk) => {
+                    generation.push_str(chunk.generation());
+                    if let Some(usage) = chunk.tokens() {
+                        tokens = Some(usage.clone());
+                    }
+                }
+                tool_call => return Ok(tool_call),
+            }
+        }
+        Ok(LLMResult::Generate(GenerateResult::new(generation, tokens)))
+    }
+
+    /// Yields a `LLMResult::Generate` per content delta.
+    /// Tool calls are streamed in pieces, so they are yielded as one `LLMResult::ToolCall` at the end.
+    /// The token usage comes with the last result.
+    async fn invoke_stream(&self, messages: &Messages) -> Result<ChatStream, LLMError> {
+        let event_source = self
+            .post(&self.build_request(messages, true))?
+            .eventsource()
+            .map_err(|e| LLMError::OtherError(format!("Failed to open event source: {}", e)))?;
+        Ok(chat_stream(stream(event_source).await))
+    }
+
+    fn add_options(&mut self, options: &CallOptions) {
+        self.options = self.options.merge(options);
+    }
+}
+
+fn to_tokens(usage: &CompletionUsage) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        total_tokens: usage.total_tokens,
+    }
+}
+
+fn to_result(response: OpenAIResponse) -> Result<LLMResult, LLMError> {
+    let tokens = response.usage.as_ref().map(to_tokens);
+    let Some(choice) = response.choices.into_iter().next() else {
+        return Err(LLMError::OtherError("No choices in response".to_string()));
+    };
+    match choice.message.tool_calls {
+        Some(tool_calls) if !tool_calls.is_empty() => {
+            to_tool_call_result(choice.message.content, tool_calls, tokens)
+        }
+        _ => Ok(LLMResult::Generate(GenerateResult::new(
+            choice.message.content.unwrap_or_default(),
+            tokens,
+        ))),
+    }
+}
+
+/// `id`, `name` and `arguments` are the ones of the first call,
+/// and the AI message keeps all calls for a `ToolNode`.
+fn to_tool_call_result(
+    content: Option<String>,
+    tool_calls: Vec<ChatCompletionMessageToolCall>,
+    tokens: Option<TokenUsage>,
+) -> Result<LLMResult, LLMError> {
+    let first = &tool_calls[0];
+    Ok(LLMResult::ToolCall(ToolCallResult {
+        id: first.id.clone(),
+        name: first.function.name.clone(),
+        arguments: serde_json::from_str(&first.function.arguments)?,
+        ai_message: Message {
+            content,
+            message_type: MessageType::AIMessage,
+            tool_calls: Some(serde_json::to_value(&tool_calls)?),
+            ..Default::default()
+        },
+        tokens,
+        metadata: None,
+    }))
+}
+
+/// A tool call whose arguments are still being streamed.
+#[derive(Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+struct StreamState {
+    responses: ChatCompletionResponseStream,
+    /// By the index of the tool call in the message.
+    tool_calls: BTreeMap<i32, PartialToolCall>,
+    tokens: Option<TokenUsage>,
+    finished: bool,
+}
+
+impl StreamState {
+    /// The result yielded after the last chunk, if anything is left to report.
+    fn finish(&mut self) -> Option<Result<LLMResult, LLMError>> {
+        self.finished = true;
+        let tokens = self.tokens.take();
+        if self.tool_calls.is_empty() {
+            return tokens.map(|tokens| {
+                Ok(LLMResult::Generate(GenerateResult::new(
+                    String::new(),
+                    Some(tokens),
+                )))
+            });
+        }
+        let tool_calls = std::mem::take(&mut self.tool_calls)
+            .into_values()
+            .map(|call| ChatCompletionMessageToolCall {
+                id: call.id,
+                kind: ChatCompletionToolType::Function,
+                function: FunctionCall {
+                    name: call.name,
+                    arguments: call.arguments,
+                },
+            })
+            .collect();
+        Some(to_tool_call_result(None, tool_calls, tokens))
+    }
+}
+
+/// The stream is polled by the caller, so that `emit_token` reaches the token sink of the caller's task.
+fn chat_stream(responses: ChatCompletionResponseStream) -> ChatStream {
+    let state = StreamState {
+        responses,
+        tool_calls: BTreeMap::new(),
+        tokens: None,
+        finished: false,
+    };
+    Box::pin(stream::unfold(state, |mut state| async move {
+        while !state.finished {
+            let response = match state.responses.next().await {
+                Some(Ok(response)) => response,
+                Some(Err(e)) => return Some((Err(e), state)),
+                None => {
+                    let result = state.finish()?;
+                    return Some((result, state));
+                }
+            };
+            if let Some(usage) = &response.usage {
+                state.tokens = Some(to_tokens(usage));
+            }
+            let Some(choice) = response.choices.into_iter().next() else {
+                continue;
+            };
+            for chunk in choice.delta.tool_calls.unwrap_or_default() {
+                let call = state
+                    .tool_calls
+                    .entry(chunk.index.unwrap_or_default())
+                    .or_default();
+                if let Some(id) = chunk.id {
+                    call.id = id;
+                }
+                if let Some(function) = chunk.function {
+                    if let Some(name) = function.name {
+                        call.name = name;
+                    }
+                    call.arguments
+                        .push_str(&function.arguments.unwrap_or_default());
+                }
+            }
+            if let Some(content) = choice.delta.content.filter(|content| !content.is_empty()) {
+                emit_token(&content);
+                let result = LLMResult::Generate(GenerateResult::new(content, None));
+                return Some((Ok(result), state));
+            }
+        }
+        None
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Result;
+    use futures::StreamExt;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    use crate::llm::{
+        CallOptions, LLM, LLMError, LLMResult, Messages, MessagesBuilder, TokenSink,
+        openai::{OpenAI, OpenAIConfigBuilder, OpenAIModel},
+        with_token_sink,
+    };
+
+    fn init_logger() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    fn mock_openai_api(status: u16, content_type: &str, body: &str) -> MockServer {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .header("authorization", "Bearer test_api_key");
+            then.status(status)
+                .header("content-type", content_type)
+                .body(body);
+        });
+        server
+    }
+
+    fn build_openai(server: &MockServer) -> Result<OpenAI> {
+        let config = OpenAIConfigBuilder::new()
+            .with_api_key("test_api_key")
+            .with_api_base(&server.url(""))
+            .with_model(OpenAIModel::Gpt4o)
+            .build()?;
+        Ok(OpenAI::new(config))
+    }
+
+    fn hello() -> Messages {
+        MessagesBuilder::new().add_human_message("Hello").build()
+    }
+
+    #[test]
+    fn test_build_request() {
+        let server = MockServer::start();
+        let openai = build_openai(&server).unwrap();
+        let request = openai.build_request(&hello(), true);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "messages": [{ "role": "user", "content": "Hello" }],
+                "model": "gpt-4o",
+                "stream": true,
+                "stream_options": { "include_usage": true },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_options() -> Result<()> {
+        init_logger();
+        let body = r#"{"id":"chatcmpl-1","object":"chat.completion","created":1743601854,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"Hi there"},"finish_reason":"stop"}]}"#;
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .json_body_includes(r#"{"temperature":0.5,"max_tokens":100}"#);
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(body);
+        });
+        let mut openai = build_openai(&server)?.with_options(
+            CallOptions::new()
+                .with_temperature(0.2)
+                .with_max_tokens(100),
+        );
+        openai.add_options(&CallOptions::new().with_temperature(0.5));
+
+        openai.invoke(&hello()).await?;
+        mock.assert();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke() -> Result<()> {
+        init_logger();
+        let body = r#"{"id":"chatcmpl-1","object":"chat.completion","created":1743601854,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"Hi there"},"finish_reason":"stop"}],"usage":{"prompt_tokens":5,"completion_tokens":2,"total_tokens":7}}"#;
+        let server = mock_openai_api(200, "application/json", body);
+        let openai = build_openai(&server)?;
+
+        match openai.invoke(&hello()).await? {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "Hi there");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_tool_calls() -> Result<()> {
+        init_logger();
+        let body = r#"{"id":"chatcmpl-2","object":"chat.completion","created":1743601854,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_abc123","type":"function","function":{"name":"get_weather","arguments":"{\"location\":\"tokyo\"}"}}]},"finish_reason":"tool_calls"}]}"#;
+        let server = mock_openai_api(200, "application/json", body);
+        let openai = build_openai(&server)?;
+
+        match openai.invoke(&hello()).await? {
+            LLMResult::ToolCall(result) => {
+                assert_eq!(result.id, "call_abc123");
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(
+                    result.ai_message.tool_calls.unwrap()[0]["function"]["name"],
+                    "get_weather"
+                );
+            }
+            _ => panic!("Expected ToolCall result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_error() -> Result<()> {
+        init_logger();
+        let body =
+            r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error"}}"#;
+        let server = mock_openai_api(401, "application/json", body);
+        let openai = build_openai(&server)?;
+        match openai.invoke(&hello()).await {
+            Err(LLMError::OtherError(message)) => assert!(message.contains("401")),
+            _ => panic!("Expected OtherError"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream() -> Result<()> {
+        init_logger();
+        let body = r#"
+data: {"id":"chatcmpl-3","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{"role":"assistant","content":""},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-3","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":"hello"},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-3","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":" world"},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-3","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}
+
+data: {"id":"chatcmpl-3","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[],"usage":{"prompt_tokens":5,"completion_tokens":2,"total_tokens":7}}
+
+data: [DONE]
+"#;
+        let server = mock_openai_api(200, "text/event-stream", body);
+        let openai = build_openai(&server)?;
+
+        let tokens = Arc::new(Mutex::new(String::new()));
+        let sink_tokens = tokens.clone();
+        let sink: TokenSink = Arc::new(move |token| sink_tokens.lock().unwrap().push_str(token));
+        let results = with_token_sink(sink, async {
+            let mut results = vec![];
+            let mut stream = openai.invoke_stream(&hello()).await?;
+            while let Some(result) = stream.next().await {
+                match result? {
+                    LLMResult::Generate(result) => results.push(result),
+                    _ => panic!("Expected Generate result"),
+                }
+            }
+            Ok::<_, LLMError>(results)
+        })
+        .await?;
+
+        let generations: Vec<_> = results.iter().map(|result| result.generation()).collect();
+        assert_eq!(generations, vec!["hello", " world", ""]);
+        assert_eq!(results[2].tokens().unwrap().total_tokens, 7);
+        assert_eq!(*tokens.lock().unwrap(), "hello world");
+
+        match openai.invoke_stream_one_result(&hello()).await? {
+            LLMResult::Generate(result) => {
+                assert_eq!(result.generation(), "hello world");
+                assert_eq!(result.tokens().unwrap().total_tokens, 7);
+            }
+            _ => panic!("Expected Generate result"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoke_stream_tool_calls() -> Result<()> {
+        init_logger();
+        // 引数は複数のチャンクに分かれて届く
+        let body = r#"
+data: {"id":"chatcmpl-4","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{"role":"assistant","content":null,"tool_calls":[{"index":0,"id":"call_abc123","type":"function","function":{"name":"get_weather","arguments":""}}]},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-4","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"location\""}}]},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-4","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":":\"tokyo\"}"}}]},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-4","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}
+
+data: {"id":"chatcmpl-4","object":"chat.completion.chunk","created":1677667095,"model":"gpt-4o","choices":[],"usage":{"prompt_tokens":20,"completion_tokens":10,"total_tokens":30}}
+
+data: [DONE]
+"#;
+        let server = mock_openai_api(200, "text/event-stream", body);
+        let openai = build_openai(&server)?;
+
+        let mut stream = openai.invoke_stream(&hello()).await?;
+        match stream.next().await {
+            Some(Ok(LLMResult::ToolCall(result))) => {
+                assert_eq!(result.id, "call_abc123");
+                assert_eq!(result.name, "get_weather");
+                assert_eq!(result.arguments, json!({ "location": "tokyo" }));
+                assert_eq!(result.tokens.unwrap().total_tokens, 30);
+            }
+            other => panic!("Expected ToolCall result {:?}", other),
+        }
+        assert!(stream.next().await.is_none());
+        Ok(())
+    }
+}
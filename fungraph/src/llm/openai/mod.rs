@@ -0,0 +1,6 @@
+pub mod schema;
+pub use schema::*;
+pub mod config;
+pub use config::*;
+pub mod llm;
+pub use llm::*;
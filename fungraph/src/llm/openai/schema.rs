@@ -0,0 +1,28 @@
+use serde::Serialize;
+
+use crate::{
+    llm::CallOptions,
+    types::openai::{OpenAIContent, Tool},
+};
+
+#[derive(Debug, Serialize)]
+pub struct StreamOptions {
+    /// Sends the token usage in a last chunk without choices.
+    pub include_usage: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenAIRequest {
+    pub messages: Vec<OpenAIContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<String>,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(flatten)]
+    pub options: CallOptions,
+}
@@ -0,0 +1,66 @@
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use reqwest_eventsource::{Event, EventSource};
+use serde::de::DeserializeOwned;
+
+use super::LLMError;
+
+/// Parses the data of each server-sent event as `O` until the `[DONE]` event or the end of the response.
+/// The connection is closed when the returned stream is dropped.
+pub(crate) async fn stream<O>(
+    mut event_source: EventSource,
+) -> Pin<Box<dyn Stream<Item = Result<O, LLMError>> + Send>>
+where
+    O: DeserializeOwned + std::marker::Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            // 受信側が破棄されたら (例: グラフの実行がキャンセルされたら) 接続を閉じる
+            let ev = tokio::select! {
+                ev = event_source.next() => ev,
+                _ = tx.closed() => break,
+            };
+            let Some(ev) = ev else {
+                break;
+            };
+            match ev {
+                // サーバーが [DONE] を送らずに接続を閉じた場合も正常終了とする
+                Err(reqwest_eventsource::Error::StreamEnded) => break,
+                Err(e) => {
+                    if let Err(_e) = tx.send(Err(LLMError::OtherError(format!(
+                        "Event source error: {}",
+                        e
+                    )))) {
+                        // rx dropped
+                        break;
+                    }
+                }
+                Ok(event) => match event {
+                    Event::Message(message) => {
+                        if message.data == "[DONE]" {
+                            break;
+                        }
+
+                        let response = match serde_json::from_str::<O>(&message.data) {
+                            Err(e) => Err(LLMError::OtherError(format!("serde_json error: {}", e))),
+                            Ok(output) => Ok(output),
+                        };
+
+                        if let Err(_e) = tx.send(response) {
+                            // rx dropped
+                            break;
+                        }
+                    }
+                    Event::Open => continue,
+                },
+            }
+        }
+
+        event_source.close();
+    });
+
+    Box::pin(tokio_stream::wrappers::UnboundedReceiverStream::new(rx))
+}
@@ -0,0 +1,44 @@
+use std::{future::Future, sync::Arc};
+
+/// Receives the text chunks streamed by `LLM::invoke_stream`.
+pub type TokenSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+tokio::task_local! {
+    static TOKEN_SINK: TokenSink;
+}
+
+/// Runs `future` with `sink` receiving every token streamed inside it.
+/// `FunGraph::stream` uses this to tag the tokens with the node that streamed them.
+pub async fn with_token_sink<F: Future>(sink: TokenSink, future: F) -> F::Output {
+    TOKEN_SINK.scope(sink, future).await
+}
+
+/// Sends `token` to the sink of the current `with_token_sink`, if any.
+pub fn emit_token(token: &str) {
+    let _ = TOKEN_SINK.try_with(|sink| sink(token));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_token_sink() {
+        let tokens = Arc::new(Mutex::new(vec![]));
+        let sink_tokens = tokens.clone();
+        let sink: TokenSink =
+            Arc::new(move |token| sink_tokens.lock().unwrap().push(token.to_string()));
+
+        // スコープ外のトークンは捨てられる
+        emit_token("ignored");
+        with_token_sink(sink, async {
+            emit_token("hello");
+            emit_token(" world");
+        })
+        .await;
+
+        assert_eq!(*tokens.lock().unwrap(), vec!["hello", " world"]);
+    }
+}
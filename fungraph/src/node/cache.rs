@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::{FunNode, FunState, NodeError, checkpoint::encode_file_name};
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON serialization/deserialization error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+}
+
+/// Storage of node results for `CachedNode`. Expired entries are treated as missing.
+#[async_trait]
+pub trait NodeCache: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Value>, CacheError>;
+    async fn put(&self, key: &str, value: Value) -> Result<(), CacheError>;
+}
+
+struct MemoryEntry {
+    value: Value,
+    created_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct MemoryEntries {
+    entries: HashMap<String, MemoryEntry>,
+    clock: u64,
+}
+
+/// Keeps up to `capacity` results in memory and evicts the least recently used one.
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    entries: Mutex<MemoryEntries>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: None,
+            entries: Mutex::new(MemoryEntries::default()),
+        }
+    }
+
+    /// Results older than `ttl` are not returned anymore.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn is_expired(&self, entry: &MemoryEntry) -> bool {
+        self.ttl.is_some_and(|ttl| entry.created_at.elapsed() > ttl)
+    }
+}
+
+#[async_trait]
+impl NodeCache for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<Value>, CacheError> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .entries
+            .get(key)
+            .is_some_and(|entry| self.is_expired(entry))
+        {
+            entries.entries.remove(key);
+        }
+        entries.clock += 1;
+        let clock = entries.clock;
+        Ok(entries.entries.get_mut(key).map(|entry| {
+            entry.last_used = clock;
+            entry.value.clone()
+        }))
+    }
+
+    async fn put(&self, key: &str, value: Value) -> Result<(), CacheError> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let entry = MemoryEntry {
+            value,
+            created_at: Instant::now(),
+            last_used: entries.clock,
+        };
+        entries.entries.insert(key.to_string(), entry);
+        while entries.entries.len() > self.capacity {
+            let Some(oldest) = entries
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.entries.remove(&oldest);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    /// Milliseconds since the UNIX epoch.
+    created_at_millis: u64,
+    value: Value,
+}
+
+/// Saves results as `<dir>/<key>.json` on the local disk, so they survive restarts.
+pub struct DiskCache {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+}
+
+impl DiskCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: None,
+        }
+    }
+
+    /// Results older than `ttl` are not returned anymore.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", encode_file_name(key)))
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[async_trait]
+impl NodeCache for DiskCache {
+    async fn get(&self, key: &str) -> Result<Option<Value>, CacheError> {
+        let content = match tokio::fs::read(self.path(key)).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let entry: DiskEntry = serde_json::from_slice(&content)?;
+        let age = Duration::from_millis(now_millis().saturating_sub(entry.created_at_millis));
+        let expired = self.ttl.is_some_and(|ttl| age > ttl);
+        Ok((!expired).then_some(entry.value))
+    }
+
+    async fn put(&self, key: &str, value: Value) -> Result<(), CacheError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(key);
+        let entry = DiskEntry {
+            created_at_millis: now_millis(),
+            value,
+        };
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec(&entry)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+}
+
+/// Runs `node` only for input states it has not seen before, and returns the cached update otherwise.
+/// The key is the node name and the SHA-256 digest of the input state serialized as JSON, whose object keys are sorted.
+/// The serialized input is stored with the update, so a hit on a colliding key is treated as a miss.
+/// Failures of the cache are logged and the node runs as if nothing was cached.
+///
+/// # Usage
+/// ```rust,ignore
+/// let cache = Arc::new(DiskCache::new(".cache/fungraph").with_ttl(Duration::from_secs(3600)));
+/// let llm = builder.add_node(CachedNode::new(LLMNode::new(llm), cache));
+/// ```
+pub struct CachedNode<N> {
+    node: N,
+    cache: Arc<dyn NodeCache>,
+}
+
+impl<N> CachedNode<N> {
+    pub fn new(node: N, cache: Arc<dyn NodeCache>) -> Self {
+        Self { node, cache }
+    }
+
+    /// Returns the cache key and the JSON of `state`.
+    fn key<S: Serialize>(&self, name: &str, state: &S) -> Result<(String, String), CacheError> {
+        // Value の Map は BTreeMap なので、HashMap のフィールドもキー順に並ぶ
+        let input = serde_json::to_string(&serde_json::to_value(state)?)?;
+        let digest: String = Sha256::digest(input.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Ok((format!("{}-{}", name, digest), input))
+    }
+}
+
+/// Value stored by `CachedNode`.
+#[derive(Serialize, Deserialize)]
+struct CachedUpdate {
+    /// JSON of the input state. A hit with another input is a collision of the key and is ignored.
+    input: String,
+    update: Value,
+}
+
+#[async_trait]
+impl<S, N> FunNode<S> for CachedNode<N>
+where
+    S: FunState,
+    S::Update: Serialize + DeserializeOwned,
+    N: FunNode<S>,
+{
+    fn get_name(&self) -> String {
+        self.node.get_name()
+    }
+
+    async fn run(&self, state: S) -> Result<S::Update, NodeError> {
+        let name = self.node.get_name();
+        let (key, input) = match self.key(&name, &state) {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("Failed to compute the cache key of node '{}': {}", name, e);
+                return self.node.run(state).await;
+            }
+        };
+        match self.cache.get(&key).await {
+            Ok(Some(value)) => match serde_json::from_value::<CachedUpdate>(value)
+                .and_then(|cached| Ok((cached.input, serde_json::from_value(cached.update)?)))
+            {
+                Ok((cached_input, update)) if cached_input == input => return Ok(update),
+                Ok(_) => warn!("Cache key of node '{}' collided, ignoring the entry", name),
+                Err(e) => warn!("Ignoring a broken cache entry of node '{}': {}", name, e),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read the cache of node '{}': {}", name, e),
+        }
+
+        let update = self.node.run(state).await?;
+        let stored = match serde_json::to_value(&update) {
+            Ok(update) => match serde_json::to_value(CachedUpdate { input, update }) {
+                Ok(value) => self.cache.put(&key, value).await,
+                Err(e) => Err(e.into()),
+            },
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = stored {
+            warn!("Failed to write the cache of node '{}': {}", name, e);
+        }
+        Ok(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::node::{Append, Reducer};
+
+    #[tokio::test]
+    async fn test_memory_cache_evicts_least_recently_used() -> Result<(), CacheError> {
+        let cache = MemoryCache::new(2);
+        cache.put("a", json!(1)).await?;
+        cache.put("b", json!(2)).await?;
+        assert_eq!(cache.get("a").await?, Some(json!(1)));
+        // b は a より長く使われていないので追い出される
+        cache.put("c", json!(3)).await?;
+        assert_eq!(cache.get("b").await?, None);
+        assert_eq!(cache.get("a").await?, Some(json!(1)));
+        assert_eq!(cache.get("c").await?, Some(json!(3)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_ttl() -> Result<(), CacheError> {
+        let cache = MemoryCache::new(10).with_ttl(Duration::from_millis(10));
+        cache.put("a", json!(1)).await?;
+        assert_eq!(cache.get("a").await?, Some(json!(1)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get("a").await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache() -> Result<(), CacheError> {
+        let dir = std::env::temp_dir().join(format!("fungraph-cache-{}", std::process::id()));
+        let cache = DiskCache::new(&dir);
+        assert_eq!(cache.get("llm-0123").await?, None);
+        cache.put("llm-0123", json!({ "answer": 42 })).await?;
+        assert_eq!(cache.get("llm-0123").await?, Some(json!({ "answer": 42 })));
+
+        // 別のインスタンスからも読める
+        let cache = DiskCache::new(&dir).with_ttl(Duration::from_secs(60));
+        assert_eq!(cache.get("llm-0123").await?, Some(json!({ "answer": 42 })));
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_ttl_below_one_second() -> Result<(), CacheError> {
+        let dir = std::env::temp_dir().join(format!("fungraph-cache-ttl-{}", std::process::id()));
+        let cache = DiskCache::new(&dir).with_ttl(Duration::from_millis(10));
+        cache.put("a", json!(1)).await?;
+        assert_eq!(cache.get("a").await?, Some(json!(1)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get("a").await?, None);
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_node_key_sorts_map_keys() {
+        let mut a = HashMap::new();
+        let mut b = HashMap::new();
+        for key in ["x", "y", "z"] {
+            a.insert(key, json!({ "b": 1, "a": [{ "d": 2, "c": 3 }] }));
+        }
+        for key in ["z", "y", "x"] {
+            b.insert(key, json!({ "a": [{ "c": 3, "d": 2 }], "b": 1 }));
+        }
+        let node = CachedNode::new((), Arc::new(MemoryCache::new(1)));
+        let (key, input) = node.key("node", &a).unwrap();
+        assert_eq!((key, input.clone()), node.key("node", &b).unwrap());
+        assert!(input.starts_with(r#"{"x":{"a":[{"c":3,"d":2}],"b":1}"#));
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct QuestionState {
+        question: String,
+        answers: Vec<String>,
+    }
+
+    impl FunState for QuestionState {
+        type Update = Vec<String>;
+
+        fn apply(&mut self, update: Vec<String>) {
+            Append::reduce(&mut self.answers, update);
+        }
+    }
+
+    struct CountingNode {
+        runs: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FunNode<QuestionState> for CountingNode {
+        fn get_name(&self) -> String {
+            "answer".to_string()
+        }
+
+        async fn run(&self, state: QuestionState) -> Result<Vec<String>, NodeError> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![format!("answer to {}", state.question)])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_node() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let node = CachedNode::new(
+            CountingNode { runs: runs.clone() },
+            Arc::new(MemoryCache::new(10)),
+        );
+        let state = QuestionState {
+            question: "why".to_string(),
+            answers: vec![],
+        };
+
+        assert_eq!(
+            node.run(state.clone()).await.unwrap(),
+            vec!["answer to why"]
+        );
+        assert_eq!(
+            node.run(state.clone()).await.unwrap(),
+            vec!["answer to why"]
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let state = QuestionState {
+            question: "how".to_string(),
+            ..state
+        };
+        assert_eq!(node.run(state).await.unwrap(), vec!["answer to how"]);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cached_node_key_collision() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let cache = Arc::new(MemoryCache::new(10));
+        let node = CachedNode::new(CountingNode { runs: runs.clone() }, cache.clone());
+        let state = QuestionState {
+            question: "why".to_string(),
+            answers: vec![],
+        };
+
+        // 別の入力の結果が同じキーに入っていても使わない
+        let (key, _) = node.key("answer", &state).unwrap();
+        let entry = json!({ "input": "{}", "update": ["answer to something else"] });
+        cache.put(&key, entry).await.unwrap();
+        assert_eq!(
+            node.run(state.clone()).await.unwrap(),
+            vec!["answer to why"]
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        assert_eq!(node.run(state).await.unwrap(), vec!["answer to why"]);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Snapshot of a `FunGraph` run, saved when a run starts and after every superstep.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    pub thread_id: String,
+    /// Number of supersteps that have finished.
+    pub step: usize,
+    /// Names of the nodes that ran in the superstep that produced this checkpoint.
+    #[serde(default)]
+    pub completed_nodes: Vec<String>,
+    /// Serialized state after the last finished superstep.
+    pub state: Value,
+    /// Names of the nodes to run next with the shared state.
+    pub next_nodes: Vec<String>,
+    /// Nodes to run next with a state of their own, from `Route::Send`.
+    #[serde(default)]
+    pub sends: Vec<(String, Value)>,
+    /// Sources that have already finished, per join target that is still waiting.
+    #[serde(default)]
+    pub barriers: BTreeMap<String, Vec<String>>,
+}
+
+impl Checkpoint {
+    /// Whether the run has no node left to run.
+    pub fn is_finished(&self) -> bool {
+        self.next_nodes.is_empty() && self.sends.is_empty()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("JSON serialization/deserialization error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+}
+
+/// A checkpoint with its state deserialized, returned by `FunGraph::get_state_history`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateSnapshot<S> {
+    pub step: usize,
+    /// Names of the nodes that ran in the superstep that produced this snapshot.
+    pub completed_nodes: Vec<String>,
+    /// Names of the nodes scheduled for the next superstep.
+    pub next_nodes: Vec<String>,
+    pub state: S,
+}
+
+impl<S: DeserializeOwned> TryFrom<Checkpoint> for StateSnapshot<S> {
+    type Error = CheckpointError;
+
+    fn try_from(checkpoint: Checkpoint) -> Result<Self, Self::Error> {
+        Ok(StateSnapshot {
+            step: checkpoint.step,
+            completed_nodes: checkpoint.completed_nodes,
+            next_nodes: checkpoint.next_nodes,
+            state: serde_json::from_value(checkpoint.state)?,
+        })
+    }
+}
+
+/// Storage of the checkpoint history of each thread.
+/// The latest checkpoint of a thread is where its next run resumes.
+#[async_trait]
+pub trait Checkpointer: Send + Sync {
+    /// Appends `checkpoint` to the history of its thread.
+    async fn put(&self, checkpoint: Checkpoint) -> Result<(), CheckpointError>;
+    /// Returns the latest checkpoint of the thread.
+    async fn get(&self, thread_id: &str) -> Result<Option<Checkpoint>, CheckpointError>;
+    /// Returns every checkpoint of the thread, oldest first.
+    async fn list(&self, thread_id: &str) -> Result<Vec<Checkpoint>, CheckpointError>;
+}
+
+/// Keeps checkpoints in memory. Useful for tests and single-process servers.
+#[derive(Default)]
+pub struct MemorySaver {
+    checkpoints: Mutex<HashMap<String, Vec<Checkpoint>>>,
+}
+
+impl MemorySaver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Checkpointer for MemorySaver {
+    async fn put(&self, checkpoint: Checkpoint) -> Result<(), CheckpointError> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .entry(checkpoint.thread_id.clone())
+            .or_default()
+            .push(checkpoint);
+        Ok(())
+    }
+
+    async fn get(&self, thread_id: &str) -> Result<Option<Checkpoint>, CheckpointError> {
+        let checkpoints = self.checkpoints.lock().unwrap();
+        Ok(checkpoints
+            .get(thread_id)
+            .and_then(|history| history.last().cloned()))
+    }
+
+    async fn list(&self, thread_id: &str) -> Result<Vec<Checkpoint>, CheckpointError> {
+        let checkpoints = self.checkpoints.lock().unwrap();
+        Ok(checkpoints.get(thread_id).cloned().unwrap_or_default())
+    }
+}
+
+/// Saves checkpoints as `<dir>/<thread_id>/<sequence number>.json` on the local disk.
+///
+/// Puts to the same thread are serialized by this `FileSaver` only.
+/// Another `FileSaver` or another process writing to the same directory may pick the same sequence number,
+/// so share one `FileSaver` per directory within a single process.
+pub struct FileSaver {
+    dir: PathBuf,
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl FileSaver {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn thread_lock(&self, thread_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.locks
+            .lock()
+            .unwrap()
+            .entry(thread_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Drops the lock of the thread unless another put holds or waits for it.
+    fn release_thread_lock(&self, thread_id: &str, lock: Arc<tokio::sync::Mutex<()>>) {
+        let mut locks = self.locks.lock().unwrap();
+        // map のエントリと `lock` の二つだけなら、他に使っている put はない
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(thread_id);
+        }
+    }
+
+    fn thread_dir(&self, thread_id: &str) -> PathBuf {
+        self.dir.join(encode_file_name(thread_id))
+    }
+
+    /// Paths of the checkpoints of the thread, oldest first.
+    async fn paths(&self, thread_id: &str) -> Result<Vec<PathBuf>, CheckpointError> {
+        let mut entries = match tokio::fs::read_dir(self.thread_dir(thread_id)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut paths = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                paths.push(path);
+            }
+        }
+        // 連番はゼロ埋めしているので、名前順が保存順になる
+        paths.sort();
+        Ok(paths)
+    }
+
+    async fn read(path: &Path) -> Result<Checkpoint, CheckpointError> {
+        Ok(serde_json::from_slice(&tokio::fs::read(path).await?)?)
+    }
+
+    /// Writes `checkpoint` as the next checkpoint of its thread. Needs the lock of the thread.
+    async fn write_next(&self, checkpoint: Checkpoint) -> Result<(), CheckpointError> {
+        let thread_dir = self.thread_dir(&checkpoint.thread_id);
+        tokio::fs::create_dir_all(&thread_dir).await?;
+        let sequence = self.paths(&checkpoint.thread_id).await?.len();
+        let path = thread_dir.join(format!("{:08}.json", sequence));
+        // 途中で強制終了されても壊れたファイルが残らないように、一時ファイルに書いてから置き換える
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(&checkpoint)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Checkpointer for FileSaver {
+    async fn put(&self, checkpoint: Checkpoint) -> Result<(), CheckpointError> {
+        // 連番をファイル一覧から決めるので、同じスレッドへの書き込みは一つずつ行う
+        let thread_id = checkpoint.thread_id.clone();
+        let lock = self.thread_lock(&thread_id);
+        let result = {
+            let _guard = lock.lock().await;
+            self.write_next(checkpoint).await
+        };
+        self.release_thread_lock(&thread_id, lock);
+        result
+    }
+
+    async fn get(&self, thread_id: &str) -> Result<Option<Checkpoint>, CheckpointError> {
+        match self.paths(thread_id).await?.last() {
+            Some(path) => Ok(Some(Self::read(path).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn list(&self, thread_id: &str) -> Result<Vec<Checkpoint>, CheckpointError> {
+        let mut checkpoints = vec![];
+        for path in self.paths(thread_id).await? {
+            checkpoints.push(Self::read(&path).await?);
+        }
+        Ok(checkpoints)
+    }
+}
+
+/// Escapes characters that are not safe in file names, e.g. `user/1` -> `user%2F1`.
+pub(crate) fn encode_file_name(name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn test_checkpoint(thread_id: &str, step: usize) -> Checkpoint {
+        Checkpoint {
+            thread_id: thread_id.to_string(),
+            step,
+            completed_nodes: vec!["a".to_string()],
+            state: json!({ "count": step }),
+            next_nodes: vec!["b".to_string()],
+            sends: vec![],
+            barriers: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_encode_file_name() {
+        assert_eq!(encode_file_name("thread-1_a"), "thread-1_a");
+        assert_eq!(encode_file_name("user/1.json"), "user%2F1%2Ejson");
+    }
+
+    #[tokio::test]
+    async fn test_memory_saver() -> Result<(), CheckpointError> {
+        let saver = MemorySaver::new();
+        assert_eq!(saver.get("thread-1").await?, None);
+
+        saver.put(test_checkpoint("thread-1", 1)).await?;
+        saver.put(test_checkpoint("thread-1", 2)).await?;
+        saver.put(test_checkpoint("thread-2", 1)).await?;
+
+        assert_eq!(saver.get("thread-1").await?.unwrap().step, 2);
+        assert_eq!(saver.get("thread-2").await?.unwrap().step, 1);
+        let history = saver.list("thread-1").await?;
+        assert_eq!(
+            history.iter().map(|c| c.step).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(saver.list("thread-3").await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_saver() -> Result<(), CheckpointError> {
+        let dir = std::env::temp_dir().join(format!("fungraph-file-saver-{}", std::process::id()));
+        let saver = FileSaver::new(&dir);
+        assert_eq!(saver.get("user/1").await?, None);
+        assert!(saver.list("user/1").await?.is_empty());
+
+        saver.put(test_checkpoint("user/1", 1)).await?;
+        saver.put(test_checkpoint("user/1", 2)).await?;
+        saver.put(test_checkpoint("user/1", 1)).await?;
+
+        // 別のインスタンスからも読み込める
+        let saver = FileSaver::new(&dir);
+        assert_eq!(
+            saver.get("user/1").await?,
+            Some(test_checkpoint("user/1", 1))
+        );
+        let history = saver.list("user/1").await?;
+        assert_eq!(
+            history.iter().map(|c| c.step).collect::<Vec<_>>(),
+            vec![1, 2, 1]
+        );
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_file_saver_concurrent_puts() -> Result<(), CheckpointError> {
+        let dir = std::env::temp_dir().join(format!(
+            "fungraph-file-saver-concurrent-{}",
+            std::process::id()
+        ));
+        let saver = Arc::new(FileSaver::new(&dir));
+
+        let handles: Vec<_> = (0..20)
+            .map(|step| {
+                let saver = saver.clone();
+                tokio::spawn(async move { saver.put(test_checkpoint("thread-1", step)).await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap()?;
+        }
+
+        let mut steps: Vec<usize> = saver
+            .list("thread-1")
+            .await?
+            .iter()
+            .map(|c| c.step)
+            .collect();
+        steps.sort();
+        assert_eq!(steps, (0..20).collect::<Vec<_>>());
+        // 書き込みが終わったスレッドのロックは残らない
+        assert!(saver.locks.lock().unwrap().is_empty());
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+}
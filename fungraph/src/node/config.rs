@@ -0,0 +1,232 @@
+use std::{future::Future, time::Duration};
+
+use futures::future;
+use tokio::time::Instant;
+pub use tokio_util::sync::CancellationToken;
+
+/// Settings for a single `FunGraph` run.
+///
+/// # Usage
+/// ```rust,ignore
+/// let config = RunConfig::default().with_recursion_limit(50);
+/// let state = graph.run_with_config(state, &config).await?;
+///
+/// // Checkpointed run that resumes where the previous run of "user-1" stopped.
+/// let graph = graph.with_checkpointer(Arc::new(FileSaver::new("checkpoints")));
+/// let config = RunConfig::default().with_thread_id("user-1");
+/// let state = graph.run_with_config(state, &config).await?;
+///
+/// // Run that stops when the client disconnects or after a minute.
+/// let token = CancellationToken::new();
+/// let config = RunConfig::default()
+///     .with_cancellation_token(token.clone())
+///     .with_timeout(Duration::from_secs(60));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RunConfig {
+    recursion_limit: usize,
+    thread_id: Option<String>,
+    cancellation_token: Option<CancellationToken>,
+    deadline: Option<Instant>,
+    /// Config of the outer run when this run is the inner run of a `SubGraph`.
+    /// Its cancellation token and deadline stop this run as well.
+    outer: Option<Box<RunConfig>>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            recursion_limit: 25,
+            thread_id: None,
+            cancellation_token: None,
+            deadline: None,
+            outer: None,
+        }
+    }
+}
+
+impl RunConfig {
+    /// Maximum number of supersteps before the run fails with `GraphError::RecursionLimit`.
+    pub fn recursion_limit(&self) -> usize {
+        self.recursion_limit
+    }
+
+    /// Key of the checkpoints of this run. Runs without a thread id are not checkpointed.
+    pub fn thread_id(&self) -> Option<&str> {
+        self.thread_id.as_deref()
+    }
+
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+
+    /// Time by which the whole run has to finish.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn with_recursion_limit(mut self, recursion_limit: usize) -> Self {
+        self.recursion_limit = recursion_limit;
+        self
+    }
+
+    pub fn with_thread_id(mut self, thread_id: &str) -> Self {
+        self.thread_id = Some(thread_id.into());
+        self
+    }
+
+    /// Cancelling `token` stops the run with `GraphError::Cancelled`.
+    /// Running nodes are dropped, which also closes their open LLM streams.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Stops the run with `GraphError::DeadlineExceeded` at `deadline`.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline to `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Also stops the run when `outer` has to stop.
+    pub(crate) fn with_outer(mut self, outer: RunConfig) -> Self {
+        self.outer = Some(Box::new(outer));
+        self
+    }
+
+    /// Returns why the run has to stop now, if it has to.
+    pub(crate) fn stop_reason(&self) -> Option<StopReason> {
+        if self
+            .cancellation_token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return Some(StopReason::Cancelled);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Some(StopReason::DeadlineExceeded);
+        }
+        self.outer.as_ref().and_then(|outer| outer.stop_reason())
+    }
+
+    /// Resolves when the run has to stop. Never resolves without a token, a deadline and an outer run.
+    pub(crate) async fn stopped(&self) -> StopReason {
+        let cancelled = async {
+            match &self.cancellation_token {
+                Some(token) => token.cancelled().await,
+                None => future::pending().await,
+            }
+        };
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => future::pending().await,
+            }
+        };
+        let outer = async {
+            match &self.outer {
+                Some(outer) => Box::pin(outer.stopped()).await,
+                None => future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = cancelled => StopReason::Cancelled,
+            _ = deadline => StopReason::DeadlineExceeded,
+            reason = outer => reason,
+        }
+    }
+}
+
+tokio::task_local! {
+    static NODE_RUN_CONFIG: RunConfig;
+}
+
+/// Runs the `future` of a node with `config` of the run it belongs to.
+pub(crate) async fn with_node_run_config<F: Future>(config: RunConfig, future: F) -> F::Output {
+    NODE_RUN_CONFIG.scope(config, future).await
+}
+
+/// Config of the run of the node that is running now, if any.
+pub(crate) fn node_run_config() -> Option<RunConfig> {
+    NODE_RUN_CONFIG.try_with(RunConfig::clone).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StopReason {
+    Cancelled,
+    DeadlineExceeded,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_config_default() {
+        let config = RunConfig::default();
+        assert_eq!(config.recursion_limit(), 25);
+        assert_eq!(config.thread_id(), None);
+        assert!(config.cancellation_token().is_none());
+        assert_eq!(config.deadline(), None);
+        assert_eq!(config.stop_reason(), None);
+    }
+
+    #[test]
+    fn test_run_config_stop_reason() {
+        let token = CancellationToken::new();
+        let config = RunConfig::default().with_cancellation_token(token.clone());
+        assert_eq!(config.stop_reason(), None);
+        token.cancel();
+        assert_eq!(config.stop_reason(), Some(StopReason::Cancelled));
+
+        let config = RunConfig::default().with_deadline(Instant::now());
+        assert_eq!(config.stop_reason(), Some(StopReason::DeadlineExceeded));
+        let config = RunConfig::default().with_timeout(Duration::from_secs(60));
+        assert_eq!(config.stop_reason(), None);
+    }
+
+    #[test]
+    fn test_run_config_stop_reason_of_outer() {
+        let token = CancellationToken::new();
+        let outer = RunConfig::default().with_cancellation_token(token.clone());
+        let config = RunConfig::default()
+            .with_timeout(Duration::from_secs(60))
+            .with_outer(outer);
+        assert_eq!(config.stop_reason(), None);
+        token.cancel();
+        assert_eq!(config.stop_reason(), Some(StopReason::Cancelled));
+
+        let outer = RunConfig::default().with_deadline(Instant::now());
+        let config = RunConfig::default().with_outer(outer);
+        assert_eq!(config.stop_reason(), Some(StopReason::DeadlineExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_run_config_stopped_by_outer() {
+        let token = CancellationToken::new();
+        let outer = RunConfig::default().with_cancellation_token(token.clone());
+        let config = RunConfig::default().with_outer(outer);
+        token.cancel();
+        assert_eq!(config.stopped().await, StopReason::Cancelled);
+    }
+
+    #[test]
+    fn test_run_config_with_thread_id() {
+        let config = RunConfig::default().with_thread_id("thread-1");
+        assert_eq!(config.thread_id(), Some("thread-1"));
+    }
+
+    #[test]
+    fn test_run_config_with_recursion_limit() {
+        let config = RunConfig::default().with_recursion_limit(3);
+        assert_eq!(config.recursion_limit(), 3);
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
+
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ConditionalEdge, DefinitionError, END, FunGraphBuilder, FunNode, FunState, Route, RouteError,
+    START,
+};
+
+/// Topology of a graph written in YAML or JSON.
+/// The nodes and routers are looked up by name in a `NodeRegistry`,
+/// so the flow can be changed without recompiling.
+/// `__start__` and `__end__` refer to the `START` and `END` markers.
+///
+/// # Usage
+/// ```yaml
+/// entry: agent
+/// nodes: [agent, tools]
+/// edges:
+///   - from: tools
+///     to: agent
+/// conditional_edges:
+///   - from: agent
+///     router: should_continue
+///     routes:
+///       continue: tools
+///       end: __end__
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GraphDefinition {
+    /// Node run first. Without it, the only node without incoming edges is used.
+    #[serde(default)]
+    pub entry: Option<String>,
+    /// Names of the nodes, in the order their updates are applied within a superstep.
+    pub nodes: Vec<String>,
+    #[serde(default)]
+    pub edges: Vec<EdgeDefinition>,
+    #[serde(default)]
+    pub joins: Vec<JoinDefinition>,
+    #[serde(default)]
+    pub conditional_edges: Vec<ConditionalEdgeDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EdgeDefinition {
+    pub from: String,
+    pub to: String,
+}
+
+/// See `FunGraphBuilder::add_join`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JoinDefinition {
+    pub sources: Vec<String>,
+    pub to: String,
+}
+
+/// Outgoing edges of `from`, chosen at run time by the registered router.
+/// The router returns a key of `routes`, and the run continues at the node of that key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConditionalEdgeDefinition {
+    pub from: String,
+    pub router: String,
+    pub routes: BTreeMap<String, String>,
+}
+
+impl GraphDefinition {
+    pub fn from_yaml(yaml: &str) -> Result<Self, DefinitionError> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, DefinitionError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Reads a `.yaml`, `.yml` or `.json` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, DefinitionError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(DefinitionError::UnsupportedFormat {
+                path: path.display().to_string(),
+            }),
+        }
+    }
+}
+
+type NamedRouter<S> = Arc<dyn Fn(&S) -> String + Send + Sync>;
+
+/// Router of a `ConditionalEdgeDefinition`. It runs the named router and looks up the returned key.
+struct KeyRouter<S> {
+    router: NamedRouter<S>,
+    routes: HashMap<String, NodeIndex>,
+}
+
+impl<S: FunState> ConditionalEdge<S> for KeyRouter<S> {
+    /// Panics on a key that has no edge. `FunGraph` calls `try_route` and reports it as `GraphError::UnknownRouteKey`.
+    fn route(&self, state: &S) -> Route<S> {
+        match self.try_route(state) {
+            Ok(route) => route,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    fn try_route(&self, state: &S) -> Result<Route<S>, RouteError> {
+        let key = (self.router)(state);
+        match self.routes.get(&key) {
+            Some(node) => Ok(Route::To(*node)),
+            None => Err(RouteError::UnknownKey { key }),
+        }
+    }
+}
+
+/// Named nodes and routers that a `GraphDefinition` can refer to.
+pub struct NodeRegistry<S: FunState> {
+    nodes: HashMap<String, Box<dyn FunNode<S>>>,
+    routers: HashMap<String, NamedRouter<S>>,
+}
+
+impl<S: FunState> Default for NodeRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: FunState> NodeRegistry<S> {
+    pub fn new() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            routers: HashMap::new(),
+        }
+    }
+
+    /// Registers `node` under its `get_name`.
+    pub fn with_node<N: FunNode<S> + 'static>(mut self, node: N) -> Self {
+        self.nodes.insert(node.get_name(), Box::new(node));
+        self
+    }
+
+    /// Registers a router that returns a key of the `routes` of a conditional edge.
+    pub fn with_router<F>(mut self, name: &str, router: F) -> Self
+    where
+        F: Fn(&S) -> String + Send + Sync + 'static,
+    {
+        self.routers.insert(name.to_string(), Arc::new(router));
+        self
+    }
+}
+
+impl<S: FunState> FunGraphBuilder<S> {
+    /// Builds the graph described by `definition` with the nodes and routers of `registry`.
+    /// Every name in `definition` must be registered. The returned builder can be configured further
+    /// (e.g. with retry policies) before `compile`, which checks the topology itself.
+    pub fn from_definition(
+        definition: &GraphDefinition,
+        mut registry: NodeRegistry<S>,
+    ) -> Result<Self, DefinitionError> {
+        let mut builder = FunGraphBuilder::new();
+        let mut indices = HashMap::from([
+            (START.to_string(), builder.start()),
+            (END.to_string(), builder.end()),
+        ]);
+        for name in definition.nodes.iter() {
+            if indices.contains_key(name) {
+                return Err(DefinitionError::DuplicateNode { node: name.clone() });
+            }
+            let node = registry
+                .nodes
+                .remove(name)
+                .ok_or_else(|| DefinitionError::NodeNotRegistered { node: name.clone() })?;
+            indices.insert(name.clone(), builder.add_boxed_node(node));
+        }
+        let index = |name: &String| -> Result<NodeIndex, DefinitionError> {
+            indices
+                .get(name)
+                .copied()
+                .ok_or_else(|| DefinitionError::UndeclaredNode { node: name.clone() })
+        };
+
+        if let Some(entry) = &definition.entry {
+            builder.add_edge(builder.start(), index(entry)?, START.to_string());
+        }
+        for edge in definition.edges.iter() {
+            builder.add_edge(
+                index(&edge.from)?,
+                index(&edge.to)?,
+                format!("{} -> {}", edge.from, edge.to),
+            );
+        }
+        for join in definition.joins.iter() {
+            let sources = join
+                .sources
+                .iter()
+                .map(index)
+                .collect::<Result<Vec<_>, _>>()?;
+            builder.add_join(&sources, index(&join.to)?, format!("join -> {}", join.to));
+        }
+        for conditional in definition.conditional_edges.iter() {
+            let from = index(&conditional.from)?;
+            let router = registry
+                .routers
+                .get(&conditional.router)
+                .cloned()
+                .ok_or_else(|| DefinitionError::RouterNotRegistered {
+                    router: conditional.router.clone(),
+                })?;
+            let mut routes = HashMap::new();
+            for (key, to) in conditional.routes.iter() {
+                let to_index = index(to)?;
+                builder.add_edge(from, to_index, key.clone());
+                routes.insert(key.clone(), to_index);
+            }
+            builder.add_conditional_edges(from, KeyRouter { router, routes });
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::node::{Append, GraphError, NodeError, Reducer};
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct DraftState {
+        drafts: Vec<String>,
+    }
+
+    impl FunState for DraftState {
+        type Update = Vec<String>;
+
+        fn apply(&mut self, update: Vec<String>) {
+            Append::reduce(&mut self.drafts, update);
+        }
+    }
+
+    struct WriteNode {
+        name: &'static str,
+    }
+
+    #[async_trait]
+    impl FunNode<DraftState> for WriteNode {
+        fn get_name(&self) -> String {
+            self.name.to_string()
+        }
+
+        async fn run(&self, _state: DraftState) -> Result<Vec<String>, NodeError> {
+            Ok(vec![self.name.to_string()])
+        }
+    }
+
+    const REVIEW_FLOW: &str = r#"
+entry: write
+nodes: [write, review]
+edges:
+  - from: write
+    to: review
+conditional_edges:
+  - from: review
+    router: enough_drafts
+    routes:
+      retry: write
+      done: __end__
+"#;
+
+    fn draft_registry(limit: usize) -> NodeRegistry<DraftState> {
+        NodeRegistry::new()
+            .with_node(WriteNode { name: "write" })
+            .with_node(WriteNode { name: "review" })
+            .with_router("enough_drafts", move |state: &DraftState| {
+                if state.drafts.len() >= limit {
+                    "done".to_string()
+                } else {
+                    "retry".to_string()
+                }
+            })
+    }
+
+    #[tokio::test]
+    async fn test_from_definition() {
+        let definition = GraphDefinition::from_yaml(REVIEW_FLOW).unwrap();
+        let graph = FunGraphBuilder::from_definition(&definition, draft_registry(4))
+            .unwrap()
+            .compile()
+            .unwrap();
+        let state = graph.run(DraftState::default()).await.unwrap();
+        assert_eq!(state.drafts, vec!["write", "review", "write", "review"]);
+
+        // JSON でも同じグラフになる
+        let json = serde_json::to_string(&definition).unwrap();
+        assert_eq!(GraphDefinition::from_json(&json).unwrap(), definition);
+    }
+
+    #[tokio::test]
+    async fn test_from_definition_unknown_route() {
+        let definition = GraphDefinition::from_yaml(REVIEW_FLOW).unwrap();
+        let registry =
+            draft_registry(4).with_router("enough_drafts", |_: &DraftState| "publish".to_string());
+        let graph = FunGraphBuilder::from_definition(&definition, registry)
+            .unwrap()
+            .compile()
+            .unwrap();
+        match graph.run(DraftState::default()).await {
+            Err(GraphError::UnknownRouteKey { from, key }) => {
+                assert_eq!(from, "review");
+                assert_eq!(key, "publish");
+            }
+            _ => panic!("Expected UnknownRouteKey"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown route key 'publish'")]
+    fn test_key_router_route_unknown_key() {
+        let router = KeyRouter {
+            router: Arc::new(|_: &DraftState| "publish".to_string()),
+            routes: HashMap::new(),
+        };
+        router.route(&DraftState::default());
+    }
+
+    #[test]
+    fn test_from_definition_not_registered() {
+        let definition = GraphDefinition::from_yaml(REVIEW_FLOW).unwrap();
+        let registry = NodeRegistry::new().with_node(WriteNode { name: "write" });
+        match FunGraphBuilder::from_definition(&definition, registry) {
+            Err(DefinitionError::NodeNotRegistered { node }) => assert_eq!(node, "review"),
+            _ => panic!("Expected NodeNotRegistered"),
+        }
+
+        let registry = NodeRegistry::new()
+            .with_node(WriteNode { name: "write" })
+            .with_node(WriteNode { name: "review" });
+        match FunGraphBuilder::from_definition(&definition, registry) {
+            Err(DefinitionError::RouterNotRegistered { router }) => {
+                assert_eq!(router, "enough_drafts")
+            }
+            _ => panic!("Expected RouterNotRegistered"),
+        }
+
+        let mut definition = definition;
+        definition.edges.push(EdgeDefinition {
+            from: "review".to_string(),
+            to: "publish".to_string(),
+        });
+        match FunGraphBuilder::from_definition(&definition, draft_registry(4)) {
+            Err(DefinitionError::UndeclaredNode { node }) => assert_eq!(node, "publish"),
+            _ => panic!("Expected UndeclaredNode"),
+        }
+    }
+}
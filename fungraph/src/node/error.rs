@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use petgraph::graph::NodeIndex;
+use thiserror::Error;
+
+use crate::llm::LLMError;
+
+use super::{CheckpointError, ResumeHandle};
+
+/// Error returned by `FunNode::run`.
+#[derive(Error, Debug)]
+pub enum NodeError {
+    #[error("LLM error: {0}")]
+    LLMError(#[from] LLMError),
+
+    #[error("Error: {0}")]
+    OtherError(String),
+
+    #[error("Any error: {0}")]
+    AnyhowError(#[from] anyhow::Error),
+
+    /// The inner graph of a `SubGraph` failed.
+    /// `source` is the error of the inner node, if the inner run failed in a node.
+    #[error("Subgraph '{graph}' failed: {message}")]
+    SubGraphError {
+        graph: String,
+        message: String,
+        #[source]
+        source: Option<Box<NodeError>>,
+    },
+
+    /// The inner graph of a `SubGraph` paused at an interrupt.
+    /// The outer graph cannot resume it, so interrupt at the subgraph node instead.
+    #[error("Subgraph '{graph}' was interrupted at {:?}", handle.nodes)]
+    SubGraphInterrupted { graph: String, handle: ResumeHandle },
+
+    #[error("Node timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Node panicked: {0}")]
+    Panic(String),
+}
+
+impl NodeError {
+    /// Whether the error may go away by running the node again, e.g. a network error or a timeout.
+    /// A subgraph error is transient when the error of its inner node is.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            NodeError::SubGraphError {
+                source: Some(source),
+                ..
+            } => source.is_transient(),
+            _ => matches!(
+                self,
+                NodeError::Timeout(_)
+                    | NodeError::LLMError(
+                        LLMError::RequestError(_)
+                            | LLMError::Timeout(_)
+                            | LLMError::IoError(_)
+                            | LLMError::EventSourceError(_)
+                    )
+            ),
+        }
+    }
+}
+
+/// Error returned by `FunGraph::run`.
+#[derive(Error, Debug)]
+pub enum GraphError<S> {
+    /// `state` is the input of the failed node, i.e. the state after the last successful node.
+    #[error("Node '{node}' failed: {source}")]
+    NodeError {
+        node: String,
+        state: S,
+        #[source]
+        source: NodeError,
+    },
+
+    /// The node failed on every attempt of its `RetryPolicy`. `source` is the last error.
+    #[error("Node '{node}' failed after {attempts} attempts: {source}")]
+    RetryExhausted {
+        node: String,
+        attempts: usize,
+        state: S,
+        #[source]
+        source: NodeError,
+    },
+
+    #[error("Router of node '{from}' selected node '{to}', but there is no edge between them")]
+    RouteNotFound { from: String, to: String },
+
+    #[error("Router of node '{from}' returned unknown route key '{key}'")]
+    UnknownRouteKey { from: String, key: String },
+
+    #[error("Recursion limit of {limit} steps reached without hitting the END node")]
+    RecursionLimit { limit: usize, state: S },
+
+    /// The cancellation token of the run was cancelled.
+    /// `state` is the state after the last finished superstep.
+    #[error("Run was cancelled")]
+    Cancelled { state: S },
+
+    /// The deadline of the run has passed.
+    /// `state` is the state after the last finished superstep.
+    #[error("Run exceeded its deadline")]
+    DeadlineExceeded { state: S },
+
+    #[error("Node '{node}' is not found")]
+    NodeNotFound { node: String },
+
+    #[error("Checkpoint error: {0}")]
+    CheckpointError(#[from] CheckpointError),
+
+    /// The run paused at an interrupt node. Pass `handle` to `FunGraph::resume` to continue it.
+    #[error("Run interrupted at {:?}", handle.nodes)]
+    Interrupted { state: S, handle: ResumeHandle },
+
+    #[error("Interrupt at node '{node}' needs a checkpointer and a thread id")]
+    CheckpointerNotSet { node: String },
+
+    #[error("No paused run matches the resume handle of thread '{thread_id}'")]
+    InvalidResumeHandle { thread_id: String },
+
+    #[error("Thread '{thread_id}' has no checkpoint of step {step}")]
+    CheckpointNotFound { thread_id: String, step: usize },
+}
+
+/// Error returned by `ConditionalEdge::try_route`.
+#[derive(Error, Debug, PartialEq)]
+pub enum RouteError {
+    #[error("Unknown route key '{key}'")]
+    UnknownKey { key: String },
+}
+
+/// Error returned by `FunGraphBuilder::compile` for a graph that cannot run.
+#[derive(Error, Debug, PartialEq)]
+pub enum CompileError {
+    #[error("Begin node is not found. Add an edge from START to the entry node")]
+    BeginNodeNotFound,
+
+    #[error("Node '{node}' is not reachable from START")]
+    UnreachableNode { node: String },
+
+    #[error("Node '{node}' is a dead end without a path to END")]
+    DeadEnd { node: String },
+
+    #[error("Conditional edges of node '{from}' have no target node")]
+    DanglingConditionalEdge { from: String },
+
+    /// `target_index` is the `NodeIndex` of a node that was added to another builder.
+    #[error(
+        "Conditional edges of node '{from}' declare target node #{} that is not in the graph",
+        target_index.index()
+    )]
+    ConditionalTargetNotFound {
+        from: String,
+        target_index: NodeIndex,
+    },
+
+    #[error("Node name '{name}' is used by more than one node")]
+    DuplicateNodeName { name: String },
+}
+
+/// Error returned while loading a `GraphDefinition` into a `FunGraphBuilder`.
+#[derive(Error, Debug)]
+pub enum DefinitionError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("YAML error: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Unsupported graph definition file '{path}'. Use .yaml, .yml or .json")]
+    UnsupportedFormat { path: String },
+
+    #[error("Node '{node}' is listed more than once")]
+    DuplicateNode { node: String },
+
+    #[error("Node '{node}' is not registered")]
+    NodeNotRegistered { node: String },
+
+    #[error("Router '{router}' is not registered")]
+    RouterNotRegistered { router: String },
+
+    #[error("Node '{node}' is used by an edge but not listed in nodes")]
+    UndeclaredNode { node: String },
+}
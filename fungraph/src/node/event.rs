@@ -0,0 +1,29 @@
+use super::GraphError;
+
+/// Progress of a run, yielded by `FunGraph::stream`.
+#[derive(Debug)]
+pub enum GraphEvent<S> {
+    NodeStarted {
+        node: String,
+        step: usize,
+    },
+    /// `state` is the state right after the update of `node` was applied.
+    NodeFinished {
+        node: String,
+        step: usize,
+        state: S,
+    },
+    EdgeTaken {
+        from: String,
+        to: String,
+    },
+    /// A chunk streamed by `LLM::invoke_stream` while `node` was running.
+    Token {
+        node: String,
+        token: String,
+    },
+    /// Last event of the stream, with what `FunGraph::run` would have returned.
+    RunFinished {
+        result: Result<S, GraphError<S>>,
+    },
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether a run paused before or after its interrupt nodes ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interrupt {
+    Before,
+    After,
+}
+
+/// Returned with `GraphError::Interrupted` and passed to `FunGraph::resume` to continue the run.
+///
+/// The paused run is stored by the checkpointer, so the handle can be serialized
+/// and resumed by another process that builds the same graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeHandle {
+    pub thread_id: String,
+    /// Names of the interrupt nodes the run paused at.
+    pub nodes: Vec<String>,
+    pub interrupt: Interrupt,
+    /// Number of supersteps that finished before the pause.
+    pub step: usize,
+}
@@ -1,4 +1,13 @@
-use crate::llm::{LLM, MessagesBuilder};
+use async_trait::async_trait;
+use log::debug;
+
+use crate::{
+    llm::{LLM, Message, Messages, MessagesBuilder},
+    tools::Tool,
+    types::openai,
+};
+
+use super::{FunNode, MessagesState, NodeError};
 
 // llmに入力し、出力する処理を実装する
 pub struct SimpleLLM<T: LLM> {
@@ -9,9 +18,220 @@ impl<T> SimpleLLM<T>
 where
     T: LLM,
 {
-    pub async fn run(&self, message: &str) -> String {
+    pub async fn run(&self, message: &str) -> Result<String, NodeError> {
         let messages = MessagesBuilder::new().add_human_message(message).build();
-        self.llm.invoke(&messages).await.unwrap();
-        "".to_string()
+        let result = self.llm.invoke(&messages).await?;
+        Ok(result.ai_message().content.unwrap_or_default())
+    }
+}
+
+/// Sends the chat history of the state to the LLM with the bound tools,
+/// and appends the AI message (including its tool calls) to the history.
+/// The token usage of the call is recorded with `MessagesState::record_token_usage`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let agent = builder.add_node(
+///     ChatModelNode::new("agent", llm)
+///         .with_system_prompt("You are a weather assistant.")
+///         .with_tool(&WeatherTool {}),
+/// );
+/// ```
+pub struct ChatModelNode<T: LLM> {
+    name: String,
+    llm: T,
+    system_prompt: Option<String>,
+    tools: Vec<openai::Tool>,
+}
+
+impl<T> ChatModelNode<T>
+where
+    T: LLM,
+{
+    pub fn new(name: &str, llm: T) -> Self {
+        Self {
+            name: name.to_string(),
+            llm,
+            system_prompt: None,
+            tools: vec![],
+        }
+    }
+
+    /// Sent before the chat history on every call. It is not stored in the state.
+    pub fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+        self.system_prompt = Some(system_prompt.to_string());
+        self
+    }
+
+    /// Lets the LLM call `tool`. The call itself is run by a `ToolNode`.
+    pub fn with_tool(mut self, tool: &dyn Tool) -> Self {
+        self.tools.push(tool.to_openai_tool());
+        self
+    }
+
+    fn build_messages(&self, history: &[Message]) -> Messages {
+        let mut builder = MessagesBuilder::new();
+        if let Some(system_prompt) = &self.system_prompt {
+            builder = builder.add_system_message(system_prompt);
+        }
+        let mut messages = builder.add_tools(self.tools.clone()).build();
+        messages.messages.extend(history.iter().cloned());
+        messages
+    }
+}
+
+#[async_trait]
+impl<S, T> FunNode<S> for ChatModelNode<T>
+where
+    S: MessagesState,
+    T: LLM,
+{
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn run(&self, state: S) -> Result<S::Update, NodeError> {
+        let messages = self.build_messages(state.messages());
+        let result = self.llm.invoke(&messages).await?;
+        debug!("ChatModelNode: {:?}", result);
+        let update = S::append_messages(vec![result.ai_message()]);
+        Ok(match result.tokens() {
+            Some(tokens) => S::record_token_usage(update, tokens.clone()),
+            None => update,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        llm::{GenerateResult, LLMResult, MessageType, ToolCallResult, fake::FakeLLM},
+        node::{Append, FunState, Reducer, Sum},
+        types::{TokenUsage, openai::Parameters},
+    };
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct ChatState {
+        messages: Vec<Message>,
+        usage: TokenUsage,
+    }
+
+    #[derive(Default)]
+    struct ChatStateUpdate {
+        messages: Option<Vec<Message>>,
+        usage: Option<TokenUsage>,
+    }
+
+    impl FunState for ChatState {
+        type Update = ChatStateUpdate;
+
+        fn apply(&mut self, update: ChatStateUpdate) {
+            Append::reduce_option(&mut self.messages, update.messages);
+            Sum::reduce_option(&mut self.usage, update.usage);
+        }
+    }
+
+    impl MessagesState for ChatState {
+        fn messages(&self) -> &[Message] {
+            &self.messages
+        }
+
+        fn append_messages(messages: Vec<Message>) -> ChatStateUpdate {
+            ChatStateUpdate {
+                messages: Some(messages),
+                ..Default::default()
+            }
+        }
+
+        fn record_token_usage(update: ChatStateUpdate, usage: TokenUsage) -> ChatStateUpdate {
+            ChatStateUpdate {
+                usage: Some(usage),
+                ..update
+            }
+        }
+    }
+
+    struct WeatherTool;
+
+    #[async_trait]
+    impl Tool for WeatherTool {
+        fn name(&self) -> &'static str {
+            "get_weather"
+        }
+
+        fn description(&self) -> &'static str {
+            "Get the current weather in a given location"
+        }
+
+        fn parameters(&self) -> Parameters {
+            Parameters {
+                r#type: "object".to_string(),
+                properties: HashMap::new(),
+                required: vec![],
+            }
+        }
+
+        async fn call(&self, _input: &Value) -> anyhow::Result<String> {
+            Ok("sunny".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_model_node() {
+        let tool_calls = json!([{
+            "id": "call_1",
+            "type": "function",
+            "function": { "name": "get_weather", "arguments": "{}" },
+        }]);
+        let llm = FakeLLM::new()
+            .with_result(LLMResult::ToolCall(ToolCallResult {
+                id: "call_1".to_string(),
+                name: "get_weather".to_string(),
+                arguments: json!({}),
+                ai_message: Message::new_ai_message("").with_tool_calls(tool_calls.clone()),
+                tokens: Some(TokenUsage::new(10, 5)),
+                metadata: None,
+            }))
+            .with_result(LLMResult::Generate(GenerateResult::new(
+                "It is sunny.".to_string(),
+                Some(TokenUsage::new(20, 3)),
+            )));
+        let node = ChatModelNode::new("agent", llm)
+            .with_system_prompt("You are a weather assistant.")
+            .with_tool(&WeatherTool);
+
+        let mut state = ChatState {
+            messages: vec![Message::new_human_message("weather?")],
+            ..Default::default()
+        };
+        state.apply(node.run(state.clone()).await.unwrap());
+        assert!(state.has_tool_calls());
+        assert_eq!(state.messages[1].tool_calls, Some(tool_calls));
+
+        state
+            .messages
+            .push(Message::new_tool_message("sunny", "call_1"));
+        state.apply(node.run(state.clone()).await.unwrap());
+        let last = state.messages.last().unwrap();
+        assert_eq!(last.message_type, MessageType::AIMessage);
+        assert_eq!(last.content.as_deref(), Some("It is sunny."));
+        assert_eq!(state.usage.total_tokens, 38);
+
+        // システムプロンプトとツールは毎回送るが、状態には保存しない
+        let requests = node.llm.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].messages.len(), 4);
+        assert_eq!(
+            requests[1].messages[0].message_type,
+            MessageType::SystemMessage
+        );
+        assert_eq!(requests[1].tools[0].function.name, "get_weather");
+        assert_eq!(state.messages.len(), 4);
     }
 }
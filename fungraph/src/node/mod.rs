@@ -1,5 +1,40 @@
+#[allow(clippy::module_inception)]
 pub mod node;
+pub use fungraph_derive::FunState;
 pub use node::*;
 
+pub mod error;
+pub use error::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod reducer;
+pub use reducer::*;
+
+pub mod checkpoint;
+pub use checkpoint::*;
+
+pub mod cache;
+pub use cache::*;
+
+pub mod interrupt;
+pub use interrupt::*;
+
+pub mod retry;
+pub use retry::*;
+
+pub mod event;
+pub use event::*;
+
+pub mod definition;
+pub use definition::*;
+
+pub mod subgraph;
+pub use subgraph::*;
+
 pub mod llmnode;
 pub use llmnode::*;
+
+pub mod toolnode;
+pub use toolnode::*;
@@ -1,7 +1,38 @@
 // node trait
 
+use std::{
+    any::Any,
+    collections::{BTreeSet, HashMap, HashSet},
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
 use async_trait::async_trait;
+use futures::{
+    FutureExt, Stream, StreamExt,
+    future::{self, join_all},
+    stream,
+};
+use log::warn;
 use petgraph::{Direction, Graph, graph::NodeIndex};
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use crate::llm::{TokenSink, with_token_sink};
+
+use super::{
+    Checkpoint, CheckpointError, Checkpointer, CompileError, GraphError, GraphEvent, Interrupt,
+    NodeError, ResumeHandle, RetryPolicy, RouteError, RunConfig, StateSnapshot,
+    config::{StopReason, with_node_run_config},
+};
+
+/// Name of the marker node where a run starts.
+pub const START: &str = "__start__";
+/// Name of the marker node where a run ends.
+pub const END: &str = "__end__";
 
 #[derive(Debug, Clone)]
 pub struct State {
@@ -9,12 +40,22 @@ pub struct State {
     pub value: String,
 }
 
-pub trait FunState {}
+/// State shared by the nodes of a `FunGraph`.
+///
+/// Nodes return a partial `Update` instead of a whole state,
+/// and `apply` merges it into the state with a `Reducer` per field.
+/// The state is serializable so that runs can be checkpointed,
+/// and `Sync` so that a whole graph can run as a node of another graph (see `SubGraph`).
+pub trait FunState: Clone + Send + Sync + Serialize + DeserializeOwned + 'static {
+    type Update: Send + 'static;
+
+    fn apply(&mut self, update: Self::Update);
+}
 
 #[async_trait]
-pub trait FunNode<S: FunState> {
+pub trait FunNode<S: FunState>: Send + Sync {
     fn get_name(&self) -> String;
-    async fn run(&self, state: S) -> S;
+    async fn run(&self, state: S) -> Result<S::Update, NodeError>;
 }
 
 pub enum FunEdgeType {
@@ -22,94 +63,828 @@ pub enum FunEdgeType {
     ConditionalEdge,
 }
 
-pub trait ConditionalEdge {
-    fn check(&self) -> bool;
+/// Destination chosen by a `ConditionalEdge`.
+pub enum Route<S> {
+    /// Runs the node with the shared state.
+    To(NodeIndex),
+    /// Runs each node with its own state, all in the next superstep.
+    /// The updates they return are reduced into the shared state like those of any other node.
+    /// A node may appear more than once, e.g. to summarize every document in parallel.
+    Send(Vec<(NodeIndex, S)>),
+}
+
+impl<S> From<NodeIndex> for Route<S> {
+    fn from(node: NodeIndex) -> Self {
+        Route::To(node)
+    }
+}
+
+/// Router of a conditional edge.
+/// It inspects the state after the source node has run and returns the next node,
+/// or a `Route::Send` to fan out over a variable number of invocations.
+///
+/// # Usage
+/// ```rust,ignore
+/// builder.add_conditional_edges(a, move |state: &MyState| if state.done { c } else { b });
+///
+/// builder.add_conditional_edges(split, move |state: &MyState| {
+///     Route::Send(state.documents.iter().map(|doc| (summarize, MyState::for_document(doc))).collect())
+/// });
+/// ```
+pub trait ConditionalEdge<S: FunState>: Send + Sync {
+    fn route(&self, state: &S) -> Route<S>;
+
+    /// Called by the graph instead of `route`.
+    /// Routers that can fail, e.g. ones that map a returned key to a node, override it.
+    fn try_route(&self, state: &S) -> Result<Route<S>, RouteError> {
+        Ok(self.route(state))
+    }
+}
+
+impl<S, F, R> ConditionalEdge<S> for F
+where
+    S: FunState,
+    F: Fn(&S) -> R + Send + Sync,
+    R: Into<Route<S>>,
+{
+    fn route(&self, state: &S) -> Route<S> {
+        self(state).into()
+    }
+}
+
+/// `START` and `END` are stored in the graph as marker nodes so that edges can point at them.
+/// They are never run.
+struct MarkerNode {
+    name: &'static str,
+}
+
+#[async_trait]
+impl<S: FunState> FunNode<S> for MarkerNode {
+    fn get_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    async fn run(&self, _state: S) -> Result<S::Update, NodeError> {
+        Err(NodeError::OtherError(format!(
+            "Marker node '{}' cannot be run",
+            self.name
+        )))
+    }
 }
 
 pub struct FunGraph<S: FunState> {
     graph: Graph<Box<dyn FunNode<S>>, String>,
+    routers: HashMap<NodeIndex, Box<dyn ConditionalEdge<S>>>,
+    joins: HashMap<NodeIndex, HashSet<NodeIndex>>,
+    interrupt_before: BTreeSet<NodeIndex>,
+    interrupt_after: BTreeSet<NodeIndex>,
+    retry_policies: HashMap<NodeIndex, RetryPolicy>,
+    timeouts: HashMap<NodeIndex, Duration>,
+    checkpointer: Option<Arc<dyn Checkpointer>>,
+    start: NodeIndex,
+    end: NodeIndex,
 }
 
 impl<S> FunGraph<S>
 where
     S: FunState,
 {
-    pub fn new() -> Self {
-        FunGraph {
-            graph: Graph::new(),
+    /// Saves a checkpoint after every superstep of runs that have a thread id.
+    /// See `RunConfig::with_thread_id`.
+    pub fn with_checkpointer(mut self, checkpointer: Arc<dyn Checkpointer>) -> Self {
+        self.checkpointer = Some(checkpointer);
+        self
+    }
+
+    pub fn edge_type(&self, from: NodeIndex) -> FunEdgeType {
+        if self.routers.contains_key(&from) {
+            FunEdgeType::ConditionalEdge
+        } else {
+            FunEdgeType::Edge
         }
     }
-    pub fn add_node<T: FunNode<S> + 'static>(&mut self, node: T) -> NodeIndex {
-        let node = Box::new(node);
-        self.graph.add_node(node)
+
+    /// Renders the graph in Graphviz DOT. Conditional edges are dashed.
+    ///
+    /// # Usage
+    /// ```sh
+    /// dot -Tpng graph.dot -o graph.png
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+        for index in self.graph.node_indices() {
+            let shape = if self.is_marker(index) {
+                "ellipse"
+            } else {
+                "box"
+            };
+            dot.push_str(&format!(
+                "    {} [label=\"{}\" shape={}];\n",
+                index.index(),
+                escape_dot(&self.get_node_name(index)),
+                shape
+            ));
+        }
+        for edge in self.graph.raw_edges() {
+            let style = match self.edge_type(edge.source()) {
+                FunEdgeType::Edge => "",
+                FunEdgeType::ConditionalEdge => " style=dashed",
+            };
+            dot.push_str(&format!(
+                "    {} -> {} [label=\"{}\"{}];\n",
+                edge.source().index(),
+                edge.target().index(),
+                escape_dot(&edge.weight),
+                style
+            ));
+        }
+        dot.push_str("}\n");
+        dot
     }
 
-    pub fn add_edge(&mut self, from: NodeIndex, to: NodeIndex, edge: String) {
-        self.graph.add_edge(from, to, edge);
+    /// Renders the graph as a Mermaid flowchart, e.g. for a PR description.
+    /// Conditional edges are dotted.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("graph TD\n");
+        for index in self.graph.node_indices() {
+            let name = escape_mermaid(&self.get_node_name(index));
+            let node = if self.is_marker(index) {
+                format!("([\"{}\"])", name)
+            } else {
+                format!("[\"{}\"]", name)
+            };
+            mermaid.push_str(&format!("    n{}{}\n", index.index(), node));
+        }
+        for edge in self.graph.raw_edges() {
+            let arrow = match self.edge_type(edge.source()) {
+                FunEdgeType::Edge => "-->",
+                FunEdgeType::ConditionalEdge => "-.->",
+            };
+            mermaid.push_str(&format!(
+                "    n{} {}|\"{}\"| n{}\n",
+                edge.source().index(),
+                arrow,
+                escape_mermaid(&edge.weight),
+                edge.target().index()
+            ));
+        }
+        mermaid
+    }
+
+    fn get_node_name(&self, index: NodeIndex) -> String {
+        self.graph
+            .node_weight(index)
+            .map(|node| node.get_name())
+            .unwrap_or_else(|| format!("{:?}", index))
     }
 
-    fn get_begin_node(&self) -> NodeIndex {
-        let indices: Vec<NodeIndex> = self
+    /// Returns the nodes that follow `current_node`.
+    /// Without a router, every outgoing edge is taken and the successors run in parallel.
+    /// Nodes with a state of their own come from a `Route::Send`.
+    fn get_next_nodes(
+        &self,
+        current_node: NodeIndex,
+        state: &S,
+    ) -> Result<Vec<(NodeIndex, Option<S>)>, GraphError<S>> {
+        let next_nodes: Vec<NodeIndex> = self
             .graph
-            .node_indices()
-            .filter(|node| {
-                self.graph
-                    .neighbors_directed(*node, Direction::Incoming)
-                    .count()
-                    == 0
-            })
+            .neighbors_directed(current_node, Direction::Outgoing)
             .collect();
+        let Some(router) = self.routers.get(&current_node) else {
+            return Ok(next_nodes.into_iter().map(|node| (node, None)).collect());
+        };
 
-        if indices.len() != 1 {
-            panic!("Begin node is not found");
+        let route = router.try_route(state).map_err(|error| match error {
+            RouteError::UnknownKey { key } => GraphError::UnknownRouteKey {
+                from: self.get_node_name(current_node),
+                key,
+            },
+        })?;
+        let routes = match route {
+            Route::To(next_node) => vec![(next_node, None)],
+            Route::Send(sends) => sends
+                .into_iter()
+                .map(|(next_node, state)| (next_node, Some(state)))
+                .collect(),
+        };
+        for (next_node, _) in routes.iter() {
+            if !next_nodes.contains(next_node) {
+                return Err(GraphError::RouteNotFound {
+                    from: self.get_node_name(current_node),
+                    to: self.get_node_name(*next_node),
+                });
+            }
         }
+        Ok(routes)
+    }
 
-        indices.first().unwrap().clone()
+    /// Schedules the nodes that follow `from` into `next`.
+    fn take_edges(
+        &self,
+        from: NodeIndex,
+        state: &S,
+        next: &mut Position<S>,
+        events: Option<&mpsc::UnboundedSender<GraphEvent<S>>>,
+    ) -> Result<(), GraphError<S>> {
+        for (to, send_state) in self.get_next_nodes(from, state)? {
+            if let Some(events) = events {
+                let _ = events.send(GraphEvent::EdgeTaken {
+                    from: self.get_node_name(from),
+                    to: self.get_node_name(to),
+                });
+            }
+            if to == self.end {
+                continue;
+            }
+            match send_state {
+                Some(send_state) => next.sends.push((to, send_state)),
+                None => self.trigger(from, to, &mut next.barriers, &mut next.nodes),
+            }
+        }
+        Ok(())
     }
 
-    fn get_end_node(&self) -> NodeIndex {
-        let indices: Vec<NodeIndex> = self
-            .graph
-            .node_indices()
-            .filter(|node| {
-                self.graph
-                    .neighbors_directed(*node, Direction::Outgoing)
-                    .count()
-                    == 0
-            })
-            .collect();
-        if indices.len() != 1 {
-            panic!("End node is not found");
+    fn is_marker(&self, index: NodeIndex) -> bool {
+        index == self.start || index == self.end
+    }
+
+    /// Returns the nodes reachable from `from` (including itself) by following edges in `direction`.
+    fn reachable(&self, from: NodeIndex, direction: Direction) -> HashSet<NodeIndex> {
+        let mut visited = HashSet::from([from]);
+        let mut stack = vec![from];
+        while let Some(index) = stack.pop() {
+            for next in self.graph.neighbors_directed(index, direction) {
+                if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        visited
+    }
+
+    /// Schedules `to` after `from` has finished.
+    /// A join target is only scheduled once all of its sources have finished.
+    fn trigger(
+        &self,
+        from: NodeIndex,
+        to: NodeIndex,
+        barriers: &mut HashMap<NodeIndex, HashSet<NodeIndex>>,
+        next_nodes: &mut BTreeSet<NodeIndex>,
+    ) {
+        match self.joins.get(&to) {
+            Some(sources) if sources.contains(&from) => {
+                let finished = barriers.entry(to).or_default();
+                finished.insert(from);
+                if finished.is_superset(sources) {
+                    barriers.remove(&to);
+                    next_nodes.insert(to);
+                }
+            }
+            _ => {
+                next_nodes.insert(to);
+            }
+        }
+    }
+
+    pub async fn run(&self, state: S) -> Result<S, GraphError<S>> {
+        self.run_with_config(state, &RunConfig::default()).await
+    }
+
+    /// Runs the graph in supersteps.
+    /// All nodes scheduled for a superstep run concurrently on a copy of the state,
+    /// and their updates are applied with `FunState::apply` in the order the nodes were added.
+    ///
+    /// With a checkpointer and a thread id, a checkpoint is saved after every superstep,
+    /// and a thread with an unfinished checkpoint resumes from it instead of starting from `state`.
+    pub async fn run_with_config(&self, state: S, config: &RunConfig) -> Result<S, GraphError<S>> {
+        self.start_run(state, config, None).await
+    }
+
+    /// Runs the graph like `run` and yields its progress as `GraphEvent`s.
+    /// The last event is always `GraphEvent::RunFinished`.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// let mut events = graph.stream(state);
+    /// while let Some(event) = events.next().await {
+    ///     if let GraphEvent::Token { node, token } = event {
+    ///         print!("{}", token);
+    ///     }
+    /// }
+    /// ```
+    pub fn stream(&self, state: S) -> Pin<Box<dyn Stream<Item = GraphEvent<S>> + Send + '_>> {
+        self.stream_with_config(state, RunConfig::default())
+    }
+
+    pub fn stream_with_config(
+        &self,
+        state: S,
+        config: RunConfig,
+    ) -> Pin<Box<dyn Stream<Item = GraphEvent<S>> + Send + '_>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // The run is polled by the returned stream itself, so dropping the stream cancels it.
+        let run = async move {
+            let result = self.start_run(state, &config, Some(&tx)).await;
+            let _ = tx.send(GraphEvent::RunFinished { result });
+        };
+        let run = stream::once(run).filter_map(|_| future::ready(None));
+        Box::pin(stream::select(UnboundedReceiverStream::new(rx), run))
+    }
+
+    async fn start_run(
+        &self,
+        state: S,
+        config: &RunConfig,
+        events: Option<&mpsc::UnboundedSender<GraphEvent<S>>>,
+    ) -> Result<S, GraphError<S>> {
+        let (state, position) = match self.load_checkpoint(config).await? {
+            Some(resumed) => resumed,
+            None => {
+                let mut position = Position::new(0, HashMap::new());
+                self.take_edges(self.start, &state, &mut position, events)?;
+                // 履歴の起点として、どのノードも実行していない状態を保存しておく
+                self.save_checkpoint(config, &state, &position, vec![])
+                    .await?;
+                (state, position)
+            }
+        };
+        self.run_from(state, position, config, false, events).await
+    }
+
+    /// Continues a run paused by `GraphError::Interrupted` with `state`,
+    /// which is usually the interrupted state after a human has reviewed or edited it.
+    pub async fn resume(&self, handle: &ResumeHandle, state: S) -> Result<S, GraphError<S>> {
+        self.resume_with_config(handle, state, &RunConfig::default())
+            .await
+    }
+
+    /// `resume` with settings. The thread id of `config` is replaced by the one of `handle`.
+    pub async fn resume_with_config(
+        &self,
+        handle: &ResumeHandle,
+        state: S,
+        config: &RunConfig,
+    ) -> Result<S, GraphError<S>> {
+        let config = config.clone().with_thread_id(&handle.thread_id);
+        let position = match self.load_checkpoint(&config).await? {
+            Some((_, position)) if position.step == handle.step => position,
+            _ => {
+                return Err(GraphError::InvalidResumeHandle {
+                    thread_id: handle.thread_id.clone(),
+                });
+            }
+        };
+        self.run_from(
+            state,
+            position,
+            &config,
+            handle.interrupt == Interrupt::Before,
+            None,
+        )
+        .await
+    }
+
+    /// Returns the checkpoints saved for `thread_id`, oldest first.
+    /// The first snapshot of a run is saved before any node has run, and the others after every superstep.
+    /// A graph without a checkpointer has no history.
+    pub async fn get_state_history(
+        &self,
+        thread_id: &str,
+    ) -> Result<Vec<StateSnapshot<S>>, GraphError<S>> {
+        let Some(checkpointer) = &self.checkpointer else {
+            return Ok(vec![]);
+        };
+        let mut history = vec![];
+        for checkpoint in checkpointer.list(thread_id).await? {
+            history.push(StateSnapshot::try_from(checkpoint)?);
         }
-        indices.first().unwrap().clone()
+        Ok(history)
+    }
+
+    /// Branches the thread off at `step` with `state`.
+    /// The latest checkpoint of `step` is saved again as the newest one with `state`,
+    /// so the next `run_with_config` of the thread replays the rest of the graph from there.
+    /// The older checkpoints stay in the history.
+    pub async fn fork(
+        &self,
+        thread_id: &str,
+        step: usize,
+        state: S,
+    ) -> Result<StateSnapshot<S>, GraphError<S>> {
+        let not_found = || GraphError::CheckpointNotFound {
+            thread_id: thread_id.to_string(),
+            step,
+        };
+        let Some(checkpointer) = &self.checkpointer else {
+            return Err(not_found());
+        };
+        let Some(mut checkpoint) = checkpointer
+            .list(thread_id)
+            .await?
+            .into_iter()
+            .rev()
+            .find(|checkpoint| checkpoint.step == step)
+        else {
+            return Err(not_found());
+        };
+        checkpoint.state = serde_json::to_value(&state).map_err(CheckpointError::from)?;
+        checkpointer.put(checkpoint.clone()).await?;
+        Ok(StateSnapshot {
+            step: checkpoint.step,
+            completed_nodes: checkpoint.completed_nodes,
+            next_nodes: checkpoint.next_nodes,
+            state,
+        })
     }
 
-    pub async fn run(&self, state: S) -> S {
-        let begin_node = self.get_begin_node();
-        let _end_node = self.get_end_node();
-        let mut current_node = begin_node;
-        let mut current_state = state;
+    /// Runs supersteps from `position` until no node is left.
+    /// `skip_interrupt_before` lets the first superstep run although it has interrupt nodes,
+    /// because the run has already paused before them.
+    async fn run_from(
+        &self,
+        mut current_state: S,
+        mut position: Position<S>,
+        config: &RunConfig,
+        mut skip_interrupt_before: bool,
+        events: Option<&mpsc::UnboundedSender<GraphEvent<S>>>,
+    ) -> Result<S, GraphError<S>> {
         loop {
-            let node = self.graph.node_weight(current_node).unwrap();
-            current_state = node.run(current_state).await;
-            let next_nodes: Vec<NodeIndex> = self
-                .graph
-                .neighbors_directed(current_node, Direction::Outgoing)
-                .collect();
-            if next_nodes.len() == 0 {
+            let scheduled = position.scheduled();
+            if scheduled.is_empty() {
                 break;
             }
-            if next_nodes.len() > 1 {
-                panic!("Multiple next nodes are not supported");
+            if position.step >= config.recursion_limit() {
+                return Err(GraphError::RecursionLimit {
+                    limit: config.recursion_limit(),
+                    state: current_state,
+                });
+            }
+            if let Some(reason) = config.stop_reason() {
+                return Err(stop_error(reason, current_state));
             }
-            current_node = next_nodes.first().unwrap().clone();
+            if !skip_interrupt_before {
+                let nodes = scheduled.intersection(&self.interrupt_before);
+                if let Some(nodes) = self.get_node_names(nodes) {
+                    // The checkpoint of this position has already been saved by the previous superstep.
+                    return self.interrupt(
+                        config,
+                        current_state,
+                        &position,
+                        Interrupt::Before,
+                        nodes,
+                    );
+                }
+            }
+            skip_interrupt_before = false;
+
+            let invocations = position
+                .nodes
+                .iter()
+                .map(|index| (*index, current_state.clone()))
+                .chain(position.sends.drain(..));
+            let tasks = invocations.map(|(index, state)| {
+                let step = position.step;
+                // SubGraph ノードは実行中の run の config から停止条件を引き継ぐ
+                with_node_run_config(config.clone(), async move {
+                    let name = self.get_node_name(index);
+                    let Some(events) = events else {
+                        return (name, self.run_node(index, state).await);
+                    };
+                    let _ = events.send(GraphEvent::NodeStarted {
+                        node: name.clone(),
+                        step,
+                    });
+                    let tokens = events.clone();
+                    let token_node = name.clone();
+                    let sink: TokenSink = Arc::new(move |token| {
+                        let _ = tokens.send(GraphEvent::Token {
+                            node: token_node.clone(),
+                            token: token.to_string(),
+                        });
+                    });
+                    (
+                        name,
+                        with_token_sink(sink, self.run_node(index, state)).await,
+                    )
+                })
+            });
+            // Dropping the running nodes also drops their LLM streams, which closes the connections.
+            // `biased` reports the stop of this run even when an inner run stopped with it at the same time.
+            let results = tokio::select! {
+                biased;
+                reason = config.stopped() => return Err(stop_error(reason, current_state)),
+                results = join_all(tasks) => results,
+            };
+
+            let mut updates = Vec::with_capacity(results.len());
+            let mut completed_nodes = Vec::with_capacity(results.len());
+            for (node, (result, exhausted)) in results {
+                match (result, exhausted) {
+                    (Ok(update), _) => {
+                        completed_nodes.push(node.clone());
+                        updates.push((node, update));
+                    }
+                    (Err(source), Some(attempts)) => {
+                        return Err(GraphError::RetryExhausted {
+                            node,
+                            attempts,
+                            state: current_state,
+                            source,
+                        });
+                    }
+                    (Err(source), None) => {
+                        return Err(GraphError::NodeError {
+                            node,
+                            state: current_state,
+                            source,
+                        });
+                    }
+                }
+            }
+            for (node, update) in updates {
+                current_state.apply(update);
+                if let Some(events) = events {
+                    let _ = events.send(GraphEvent::NodeFinished {
+                        node,
+                        step: position.step,
+                        state: current_state.clone(),
+                    });
+                }
+            }
+
+            let mut next = Position::new(position.step + 1, std::mem::take(&mut position.barriers));
+            for index in scheduled.iter() {
+                self.take_edges(*index, &current_state, &mut next, events)?;
+            }
+            let interrupted = self.get_node_names(scheduled.intersection(&self.interrupt_after));
+            position = next;
+
+            self.save_checkpoint(config, &current_state, &position, completed_nodes)
+                .await?;
+            // A finished run has nothing left to resume, so it returns normally.
+            if let Some(nodes) = interrupted.filter(|_| !position.is_finished()) {
+                return self.interrupt(config, current_state, &position, Interrupt::After, nodes);
+            }
+        }
+        Ok(current_state)
+    }
+
+    /// Runs the node at `index` with its timeout and retry policy.
+    /// Returns the result of the last attempt, with the number of attempts
+    /// when the retry policy gave up because every attempt failed with a retryable error.
+    async fn run_node(
+        &self,
+        index: NodeIndex,
+        state: S,
+    ) -> (Result<S::Update, NodeError>, Option<usize>) {
+        let node = &self.graph[index];
+        let policy = self.retry_policies.get(&index);
+        let mut attempt = 1;
+        loop {
+            let run = AssertUnwindSafe(node.run(state.clone())).catch_unwind();
+            let result = match self.timeouts.get(&index) {
+                Some(timeout) => tokio::time::timeout(*timeout, run)
+                    .await
+                    .unwrap_or(Ok(Err(NodeError::Timeout(*timeout)))),
+                None => run.await,
+            };
+            let result =
+                result.unwrap_or_else(|panic| Err(NodeError::Panic(panic_message(&panic))));
+
+            match (result, policy) {
+                (Err(error), Some(policy)) if policy.should_retry(attempt, &error) => {
+                    let interval = policy.interval(attempt);
+                    warn!(
+                        "Node '{}' failed on attempt {}, retrying in {:?}: {}",
+                        node.get_name(),
+                        attempt,
+                        interval,
+                        error
+                    );
+                    tokio::time::sleep(interval).await;
+                    attempt += 1;
+                }
+                (Err(error), Some(policy))
+                    if attempt == policy.max_attempts() && policy.is_retryable(&error) =>
+                {
+                    return (Err(error), Some(attempt));
+                }
+                (result, _) => return (result, None),
+            }
+        }
+    }
+
+    /// Returns the names of `indices`, or `None` if there are none.
+    fn get_node_names<'a>(
+        &self,
+        indices: impl Iterator<Item = &'a NodeIndex>,
+    ) -> Option<Vec<String>> {
+        let names: Vec<String> = indices.map(|index| self.get_node_name(*index)).collect();
+        if names.is_empty() { None } else { Some(names) }
+    }
+
+    fn interrupt(
+        &self,
+        config: &RunConfig,
+        state: S,
+        position: &Position<S>,
+        interrupt: Interrupt,
+        nodes: Vec<String>,
+    ) -> Result<S, GraphError<S>> {
+        let thread_id = match (&self.checkpointer, config.thread_id()) {
+            (Some(_), Some(thread_id)) => thread_id.to_string(),
+            _ => {
+                return Err(GraphError::CheckpointerNotSet {
+                    node: nodes[0].clone(),
+                });
+            }
+        };
+        Err(GraphError::Interrupted {
+            state,
+            handle: ResumeHandle {
+                thread_id,
+                nodes,
+                interrupt,
+                step: position.step,
+            },
+        })
+    }
+
+    fn find_node(&self, name: &str) -> Result<NodeIndex, GraphError<S>> {
+        self.graph
+            .node_indices()
+            .find(|index| self.graph[*index].get_name() == name)
+            .ok_or_else(|| GraphError::NodeNotFound {
+                node: name.to_string(),
+            })
+    }
+
+    /// Returns the state and position of the unfinished checkpoint of the thread, if any.
+    async fn load_checkpoint(
+        &self,
+        config: &RunConfig,
+    ) -> Result<Option<(S, Position<S>)>, GraphError<S>> {
+        let (Some(checkpointer), Some(thread_id)) = (&self.checkpointer, config.thread_id()) else {
+            return Ok(None);
+        };
+        let checkpoint = match checkpointer.get(thread_id).await? {
+            Some(checkpoint) if !checkpoint.is_finished() => checkpoint,
+            _ => return Ok(None),
+        };
+
+        let state: S = serde_json::from_value(checkpoint.state).map_err(CheckpointError::from)?;
+        let nodes = checkpoint
+            .next_nodes
+            .iter()
+            .map(|name| self.find_node(name))
+            .collect::<Result<_, _>>()?;
+        let mut sends = Vec::with_capacity(checkpoint.sends.len());
+        for (name, send_state) in checkpoint.sends {
+            let send_state = serde_json::from_value(send_state).map_err(CheckpointError::from)?;
+            sends.push((self.find_node(&name)?, send_state));
+        }
+        let mut barriers = HashMap::new();
+        for (to, sources) in checkpoint.barriers.iter() {
+            let sources = sources
+                .iter()
+                .map(|name| self.find_node(name))
+                .collect::<Result<_, _>>()?;
+            barriers.insert(self.find_node(to)?, sources);
+        }
+        Ok(Some((
+            state,
+            Position {
+                step: checkpoint.step,
+                nodes,
+                sends,
+                barriers,
+            },
+        )))
+    }
+
+    async fn save_checkpoint(
+        &self,
+        config: &RunConfig,
+        state: &S,
+        position: &Position<S>,
+        completed_nodes: Vec<String>,
+    ) -> Result<(), GraphError<S>> {
+        let (Some(checkpointer), Some(thread_id)) = (&self.checkpointer, config.thread_id()) else {
+            return Ok(());
+        };
+        let names = |indices: &mut dyn Iterator<Item = &NodeIndex>| -> Vec<String> {
+            indices.map(|index| self.get_node_name(*index)).collect()
+        };
+        let mut sends = Vec::with_capacity(position.sends.len());
+        for (index, send_state) in position.sends.iter() {
+            let send_state = serde_json::to_value(send_state).map_err(CheckpointError::from)?;
+            sends.push((self.get_node_name(*index), send_state));
+        }
+        let checkpoint = Checkpoint {
+            thread_id: thread_id.to_string(),
+            step: position.step,
+            completed_nodes,
+            state: serde_json::to_value(state).map_err(CheckpointError::from)?,
+            next_nodes: names(&mut position.nodes.iter()),
+            sends,
+            barriers: position
+                .barriers
+                .iter()
+                .map(|(to, sources)| {
+                    let mut sources = names(&mut sources.iter());
+                    sources.sort();
+                    (self.get_node_name(*to), sources)
+                })
+                .collect(),
+        };
+        checkpointer.put(checkpoint).await?;
+        Ok(())
+    }
+}
+
+fn stop_error<S>(reason: StopReason, state: S) -> GraphError<S> {
+    match reason {
+        StopReason::Cancelled => GraphError::Cancelled { state },
+        StopReason::DeadlineExceeded => GraphError::DeadlineExceeded { state },
+    }
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(label: &str) -> String {
+    label.replace('"', "#quot;")
+}
+
+/// Where a run is between two supersteps.
+struct Position<S> {
+    /// Number of finished supersteps.
+    step: usize,
+    /// Nodes of the next superstep, run with the shared state.
+    nodes: BTreeSet<NodeIndex>,
+    /// Nodes of the next superstep with a state of their own, from `Route::Send`.
+    sends: Vec<(NodeIndex, S)>,
+    /// Sources that have already finished, per join target that is still waiting.
+    barriers: HashMap<NodeIndex, HashSet<NodeIndex>>,
+}
+
+impl<S> Position<S> {
+    fn new(step: usize, barriers: HashMap<NodeIndex, HashSet<NodeIndex>>) -> Self {
+        Self {
+            step,
+            nodes: BTreeSet::new(),
+            sends: Vec::new(),
+            barriers,
         }
-        current_state
+    }
+
+    /// Every node of the next superstep, once each.
+    fn scheduled(&self) -> BTreeSet<NodeIndex> {
+        let mut scheduled = self.nodes.clone();
+        scheduled.extend(self.sends.iter().map(|(index, _)| *index));
+        scheduled
+    }
+
+    fn is_finished(&self) -> bool {
+        self.nodes.is_empty() && self.sends.is_empty()
     }
 }
 
+/// Builds a `FunGraph`. `compile` checks the structure and returns the runnable graph.
+///
+/// # Usage
+/// ```rust,ignore
+/// let mut builder = FunGraphBuilder::new();
+/// let a = builder.add_node(NodeA {});
+/// let b = builder.add_node(NodeB {});
+/// builder.add_edge(builder.start(), a, "start".to_string());
+/// builder.add_edge(a, b, "a -> b".to_string());
+/// builder.add_edge(b, builder.end(), "end".to_string());
+/// let graph = builder.compile()?;
+/// ```
 pub struct FunGraphBuilder<S: FunState> {
-    graph: Graph<Box<dyn FunNode<S>>, String>,
+    graph: FunGraph<S>,
+    conditional_targets: HashMap<NodeIndex, Vec<NodeIndex>>,
+}
+
+impl<S> Default for FunGraphBuilder<S>
+where
+    S: FunState,
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<S> FunGraphBuilder<S>
@@ -117,17 +892,1348 @@ where
     S: FunState,
 {
     pub fn new() -> Self {
+        let mut graph: Graph<Box<dyn FunNode<S>>, String> = Graph::new();
+        let start = graph.add_node(Box::new(MarkerNode { name: START }));
+        let end = graph.add_node(Box::new(MarkerNode { name: END }));
         FunGraphBuilder {
-            graph: Graph::new(),
+            graph: FunGraph {
+                graph,
+                routers: HashMap::new(),
+                joins: HashMap::new(),
+                interrupt_before: BTreeSet::new(),
+                interrupt_after: BTreeSet::new(),
+                retry_policies: HashMap::new(),
+                timeouts: HashMap::new(),
+                checkpointer: None,
+                start,
+                end,
+            },
+            conditional_targets: HashMap::new(),
         }
     }
 
+    /// Index of the `START` marker. Add an edge from it to choose the entry node explicitly.
+    pub fn start(&self) -> NodeIndex {
+        self.graph.start
+    }
+
+    /// Index of the `END` marker. A run finishes when it follows an edge to it.
+    pub fn end(&self) -> NodeIndex {
+        self.graph.end
+    }
+
     pub fn add_node<T: FunNode<S> + 'static>(&mut self, node: T) -> NodeIndex {
-        let node = Box::new(node);
-        self.graph.add_node(node)
+        self.add_boxed_node(Box::new(node))
+    }
+
+    pub(crate) fn add_boxed_node(&mut self, node: Box<dyn FunNode<S>>) -> NodeIndex {
+        self.graph.graph.add_node(node)
     }
 
     pub fn add_edge(&mut self, from: NodeIndex, to: NodeIndex, edge: String) {
-        self.graph.add_edge(from, to, edge);
+        self.graph.graph.add_edge(from, to, edge);
+    }
+
+    /// Adds edges from every node of `sources` to `to`.
+    /// `to` waits until all of `sources` have finished, even if the branches have different lengths.
+    pub fn add_join(&mut self, sources: &[NodeIndex], to: NodeIndex, edge: String) {
+        for source in sources {
+            self.graph.graph.add_edge(*source, to, edge.clone());
+        }
+        self.graph
+            .joins
+            .entry(to)
+            .or_default()
+            .extend(sources.iter().copied());
+    }
+
+    /// Makes the outgoing edges of `from` conditional.
+    /// The edges themselves are added with `add_edge`, and `router` picks one of them at run time.
+    pub fn add_conditional_edges<R: ConditionalEdge<S> + 'static>(
+        &mut self,
+        from: NodeIndex,
+        router: R,
+    ) {
+        self.graph.routers.insert(from, Box::new(router));
+    }
+
+    /// Makes the outgoing edges of `from` conditional and declares every node `router` may return.
+    /// `compile` checks that the targets are nodes of the graph and adds the missing edges to them.
+    ///
+    /// # Usage
+    /// ```rust,ignore
+    /// builder.add_conditional_edges_with_targets(
+    ///     agent,
+    ///     move |state: &MyState| if state.done { end } else { tools },
+    ///     &[tools, end],
+    /// );
+    /// ```
+    pub fn add_conditional_edges_with_targets<R: ConditionalEdge<S> + 'static>(
+        &mut self,
+        from: NodeIndex,
+        router: R,
+        targets: &[NodeIndex],
+    ) {
+        self.add_conditional_edges(from, router);
+        self.conditional_targets.insert(from, targets.to_vec());
+    }
+
+    /// Pauses runs before `node` runs, e.g. to approve a tool call.
+    /// Interrupts need a checkpointer and a thread id, see `FunGraph::with_checkpointer`.
+    pub fn interrupt_before(&mut self, node: NodeIndex) {
+        self.graph.interrupt_before.insert(node);
+    }
+
+    /// Pauses runs after `node` has run, e.g. to review its output.
+    pub fn interrupt_after(&mut self, node: NodeIndex) {
+        self.graph.interrupt_after.insert(node);
+    }
+
+    /// Runs `node` again when it fails with an error that `policy` retries.
+    pub fn set_retry_policy(&mut self, node: NodeIndex, policy: RetryPolicy) {
+        self.graph.retry_policies.insert(node, policy);
+    }
+
+    /// Fails a run of `node` with `NodeError::Timeout` when it takes longer than `timeout`.
+    /// With a retry policy, the timeout applies to each attempt.
+    pub fn set_timeout(&mut self, node: NodeIndex, timeout: Duration) {
+        self.graph.timeouts.insert(node, timeout);
+    }
+
+    /// Checks the structure of the graph and returns it.
+    ///
+    /// Without an edge from `START`, the only node without incoming edges becomes the entry node.
+    /// Every node must be reachable from `START` and must have a path to `END`.
+    pub fn compile(mut self) -> Result<FunGraph<S>, CompileError> {
+        let mut declared: Vec<(NodeIndex, Vec<NodeIndex>)> =
+            self.conditional_targets.drain().collect();
+        declared.sort();
+        for (from, targets) in declared {
+            for target in targets {
+                if self.graph.graph.node_weight(target).is_none() {
+                    return Err(CompileError::ConditionalTargetNotFound {
+                        from: self.graph.get_node_name(from),
+                        target_index: target,
+                    });
+                }
+                if self.graph.graph.find_edge(from, target).is_none() {
+                    let edge = format!(
+                        "{} -> {}",
+                        self.graph.get_node_name(from),
+                        self.graph.get_node_name(target)
+                    );
+                    self.graph.graph.add_edge(from, target, edge);
+                }
+            }
+        }
+
+        let graph = &self.graph;
+
+        let mut names = HashSet::new();
+        for index in graph.graph.node_indices() {
+            let name = graph.get_node_name(index);
+            if !names.insert(name.clone()) {
+                return Err(CompileError::DuplicateNodeName { name });
+            }
+        }
+
+        let mut routers: Vec<NodeIndex> = graph.routers.keys().copied().collect();
+        routers.sort();
+        for from in routers {
+            let has_targets = graph.graph.node_weight(from).is_some()
+                && graph
+                    .graph
+                    .neighbors_directed(from, Direction::Outgoing)
+                    .next()
+                    .is_some();
+            if !has_targets {
+                return Err(CompileError::DanglingConditionalEdge {
+                    from: graph.get_node_name(from),
+                });
+            }
+        }
+
+        let has_start_edges = graph
+            .graph
+            .neighbors_directed(graph.start, Direction::Outgoing)
+            .next()
+            .is_some();
+        if !has_start_edges {
+            let begin_nodes: Vec<NodeIndex> = graph
+                .graph
+                .node_indices()
+                .filter(|node| !graph.is_marker(*node))
+                .filter(|node| {
+                    graph
+                        .graph
+                        .neighbors_directed(*node, Direction::Incoming)
+                        .next()
+                        .is_none()
+                })
+                .collect();
+            let [begin_node] = begin_nodes[..] else {
+                return Err(CompileError::BeginNodeNotFound);
+            };
+            let start = graph.start;
+            self.graph
+                .graph
+                .add_edge(start, begin_node, START.to_string());
+        }
+
+        let graph = self.graph;
+        let reachable = graph.reachable(graph.start, Direction::Outgoing);
+        let reaches_end = graph.reachable(graph.end, Direction::Incoming);
+        for index in graph.graph.node_indices() {
+            if graph.is_marker(index) {
+                continue;
+            }
+            if !reachable.contains(&index) {
+                return Err(CompileError::UnreachableNode {
+                    node: graph.get_node_name(index),
+                });
+            }
+            if !reaches_end.contains(&index) {
+                return Err(CompileError::DeadEnd {
+                    node: graph.get_node_name(index),
+                });
+            }
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::node::{Append, CancellationToken, MemorySaver, Reducer, Sum};
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct CounterState {
+        count: i32,
+        visited: Vec<String>,
+    }
+
+    #[derive(Debug, Default)]
+    struct CounterStateUpdate {
+        count: Option<i32>,
+        visited: Option<Vec<String>>,
+    }
+
+    impl FunState for CounterState {
+        type Update = CounterStateUpdate;
+
+        fn apply(&mut self, update: CounterStateUpdate) {
+            Sum::reduce_option(&mut self.count, update.count);
+            Append::reduce_option(&mut self.visited, update.visited);
+        }
+    }
+
+    struct AddNode {
+        name: String,
+        value: i32,
+    }
+
+    impl AddNode {
+        fn new(name: &str, value: i32) -> Self {
+            Self {
+                name: name.to_string(),
+                value,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl FunNode<CounterState> for AddNode {
+        fn get_name(&self) -> String {
+            self.name.clone()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            Ok(CounterStateUpdate {
+                count: Some(self.value),
+                visited: Some(vec![self.name.clone()]),
+            })
+        }
+    }
+
+    struct FailNode;
+
+    #[async_trait]
+    impl FunNode<CounterState> for FailNode {
+        fn get_name(&self) -> String {
+            "fail".to_string()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            Err(NodeError::OtherError("something went wrong".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_linear() {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        let graph = builder.compile().unwrap();
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(state.count, 3);
+        assert_eq!(state.visited, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_conditional_edges() {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let small = builder.add_node(AddNode::new("small", 10));
+        let large = builder.add_node(AddNode::new("large", 100));
+        builder.add_edge(a, small, "small".to_string());
+        builder.add_edge(a, large, "large".to_string());
+        builder.add_edge(small, builder.end(), "end".to_string());
+        builder.add_edge(large, builder.end(), "end".to_string());
+        builder.add_conditional_edges(
+            a,
+            move |state: &CounterState| {
+                if state.count > 5 { large } else { small }
+            },
+        );
+        let graph = builder.compile().unwrap();
+
+        assert!(matches!(graph.edge_type(a), FunEdgeType::ConditionalEdge));
+        assert!(matches!(graph.edge_type(small), FunEdgeType::Edge));
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(state.visited, vec!["a", "small"]);
+
+        let initial = CounterState {
+            count: 10,
+            visited: vec![],
+        };
+        let state = graph.run(initial).await.unwrap();
+        assert_eq!(state.count, 111);
+        assert_eq!(state.visited, vec!["a", "large"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_cycle_until_end() {
+        let mut builder = FunGraphBuilder::new();
+        let llm = builder.add_node(AddNode::new("llm", 1));
+        let tools = builder.add_node(AddNode::new("tools", 10));
+        let end = builder.end();
+        builder.add_edge(builder.start(), llm, "start".to_string());
+        builder.add_edge(llm, tools, "call tools".to_string());
+        builder.add_edge(llm, end, "finish".to_string());
+        builder.add_edge(tools, llm, "tool result".to_string());
+        builder.add_conditional_edges(
+            llm,
+            move |state: &CounterState| {
+                if state.count > 20 { end } else { tools }
+            },
+        );
+        let graph = builder.compile().unwrap();
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(state.count, 23);
+        assert_eq!(state.visited, vec!["llm", "tools", "llm", "tools", "llm"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_recursion_limit() {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 1));
+        builder.add_edge(builder.start(), a, "start".to_string());
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, a, "b -> a".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        builder.add_conditional_edges(b, move |_: &CounterState| a);
+        let graph = builder.compile().unwrap();
+
+        let config = RunConfig::default().with_recursion_limit(5);
+        match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            Err(GraphError::RecursionLimit { limit, state }) => {
+                assert_eq!(limit, 5);
+                assert_eq!(state.count, 5);
+            }
+            _ => panic!("Expected RecursionLimit error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_conditional_edges_route_not_found() {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        let c = builder.add_node(AddNode::new("c", 3));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, c, "b -> c".to_string());
+        builder.add_edge(c, builder.end(), "end".to_string());
+        builder.add_conditional_edges(a, move |_: &CounterState| c);
+        let graph = builder.compile().unwrap();
+
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::RouteNotFound { from, to }) => {
+                assert_eq!(from, "a");
+                assert_eq!(to, "c");
+            }
+            _ => panic!("Expected RouteNotFound error"),
+        }
+    }
+
+    /// `a -> fail -> b`
+    fn fail_graph<N: FunNode<CounterState> + 'static>(fail: N) -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let fail = builder.add_node(fail);
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(a, fail, "a -> fail".to_string());
+        builder.add_edge(fail, b, "fail -> b".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        builder.compile().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_node_error() {
+        let graph = fail_graph(FailNode);
+
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::NodeError {
+                node,
+                state,
+                source,
+            }) => {
+                assert_eq!(node, "fail");
+                assert_eq!(state.visited, vec!["a"]);
+                assert_eq!(source.to_string(), "Error: something went wrong");
+            }
+            _ => panic!("Expected NodeError"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_fan_out_and_join() {
+        let mut builder = FunGraphBuilder::new();
+        let start = builder.start();
+        let web = builder.add_node(AddNode::new("search_web", 1));
+        let docs = builder.add_node(AddNode::new("search_docs", 2));
+        let synthesize = builder.add_node(AddNode::new("synthesize", 10));
+        builder.add_edge(start, web, "web".to_string());
+        builder.add_edge(start, docs, "docs".to_string());
+        builder.add_edge(web, synthesize, "web result".to_string());
+        builder.add_edge(docs, synthesize, "docs result".to_string());
+        builder.add_edge(synthesize, builder.end(), "end".to_string());
+        let graph = builder.compile().unwrap();
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(
+            state.visited,
+            vec!["search_web", "search_docs", "synthesize"]
+        );
+        assert_eq!(state.count, 13);
+    }
+
+    #[tokio::test]
+    async fn test_run_join_waits_for_longer_branch() {
+        let mut builder = FunGraphBuilder::new();
+        let start = builder.start();
+        let web = builder.add_node(AddNode::new("search_web", 1));
+        let docs = builder.add_node(AddNode::new("search_docs", 1));
+        let rerank = builder.add_node(AddNode::new("rerank_docs", 1));
+        let synthesize = builder.add_node(AddNode::new("synthesize", 1));
+        builder.add_edge(start, web, "web".to_string());
+        builder.add_edge(start, docs, "docs".to_string());
+        builder.add_edge(docs, rerank, "rerank".to_string());
+        builder.add_join(&[web, rerank], synthesize, "join".to_string());
+        builder.add_edge(synthesize, builder.end(), "end".to_string());
+        let graph = builder.compile().unwrap();
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(
+            state.visited,
+            vec!["search_web", "search_docs", "rerank_docs", "synthesize"]
+        );
+    }
+
+    #[test]
+    fn test_compile_begin_node_not_found() {
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, a, "b -> a".to_string());
+
+        assert_eq!(
+            builder.compile().err(),
+            Some(CompileError::BeginNodeNotFound)
+        );
+    }
+
+    #[test]
+    fn test_compile_unreachable_node() {
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        let c = builder.add_node(AddNode::new("c", 3));
+        builder.add_edge(builder.start(), a, "start".to_string());
+        builder.add_edge(a, builder.end(), "end".to_string());
+        builder.add_edge(b, c, "b -> c".to_string());
+        builder.add_edge(c, builder.end(), "end".to_string());
+
+        assert_eq!(
+            builder.compile().err(),
+            Some(CompileError::UnreachableNode {
+                node: "b".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_compile_dead_end() {
+        // b has no outgoing edge
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(a, builder.end(), "end".to_string());
+        assert_eq!(
+            builder.compile().err(),
+            Some(CompileError::DeadEnd {
+                node: "b".to_string()
+            })
+        );
+
+        // b and c loop forever
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        let c = builder.add_node(AddNode::new("c", 3));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, c, "b -> c".to_string());
+        builder.add_edge(c, b, "c -> b".to_string());
+        assert_eq!(
+            builder.compile().err(),
+            Some(CompileError::DeadEnd {
+                node: "a".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_compile_dangling_conditional_edge() {
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let end = builder.end();
+        builder.add_conditional_edges(a, move |_: &CounterState| end);
+
+        assert_eq!(
+            builder.compile().err(),
+            Some(CompileError::DanglingConditionalEdge {
+                from: "a".to_string()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compile_conditional_targets() {
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        let end = builder.end();
+        builder.add_edge(b, end, "end".to_string());
+        builder.add_conditional_edges_with_targets(
+            a,
+            move |state: &CounterState| if state.count > 5 { end } else { b },
+            &[b, end],
+        );
+        let graph = builder.compile().unwrap();
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(state.visited, vec!["a", "b"]);
+        let initial = CounterState {
+            count: 10,
+            visited: vec![],
+        };
+        let state = graph.run(initial).await.unwrap();
+        assert_eq!(state.visited, vec!["a"]);
+    }
+
+    #[test]
+    fn test_compile_conditional_target_not_found() {
+        let mut other = FunGraphBuilder::<CounterState>::new();
+        for name in ["x", "y", "z"] {
+            other.add_node(AddNode::new(name, 0));
+        }
+        // 別のグラフのノードなので、このグラフには存在しない
+        let missing = other.add_node(AddNode::new("missing", 0));
+
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let end = builder.end();
+        builder.add_conditional_edges_with_targets(
+            a,
+            move |state: &CounterState| if state.count > 5 { missing } else { end },
+            &[end, missing],
+        );
+
+        let error = builder.compile().err().unwrap();
+        assert_eq!(
+            error,
+            CompileError::ConditionalTargetNotFound {
+                from: "a".to_string(),
+                target_index: missing,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Conditional edges of node 'a' declare target node #5 that is not in the graph"
+        );
+    }
+
+    #[test]
+    fn test_compile_duplicate_node_name() {
+        let mut builder = FunGraphBuilder::<CounterState>::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("a", 2));
+        builder.add_edge(a, b, "a -> a".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+
+        assert_eq!(
+            builder.compile().err(),
+            Some(CompileError::DuplicateNodeName {
+                name: "a".to_string()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_saves_checkpoints() {
+        let saver = Arc::new(MemorySaver::new());
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        let graph = builder.compile().unwrap().with_checkpointer(saver.clone());
+
+        let config = RunConfig::default().with_thread_id("thread-1");
+        let state = graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+        assert_eq!(state.count, 3);
+
+        let checkpoint = saver.get("thread-1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.step, 2);
+        assert!(checkpoint.is_finished());
+        assert_eq!(checkpoint.state["count"], 3);
+
+        // 終了したスレッドは入力の状態から実行し直す
+        let state = graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+        assert_eq!(state.count, 3);
+        assert_eq!(saver.get("other").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_resumes_from_checkpoint() {
+        let saver = Arc::new(MemorySaver::new());
+        let config = RunConfig::default().with_thread_id("thread-1");
+
+        let graph = fail_graph(FailNode).with_checkpointer(saver.clone());
+        assert!(
+            graph
+                .run_with_config(CounterState::default(), &config)
+                .await
+                .is_err()
+        );
+
+        let checkpoint = saver.get("thread-1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.step, 1);
+        assert_eq!(checkpoint.next_nodes, vec!["fail"]);
+
+        // 同じ名前のノードを持つ別のグラフ(再起動後のプロセスを想定)で再開する
+        let graph = fail_graph(AddNode::new("fail", 10)).with_checkpointer(saver.clone());
+        let state = graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+        assert_eq!(state.visited, vec!["a", "fail", "b"]);
+        assert_eq!(state.count, 13);
+    }
+
+    /// `short` and `start -> long_1 -> long_2` are joined into `join`.
+    fn join_graph<N: FunNode<CounterState> + 'static>(
+        saver: Arc<MemorySaver>,
+        long_2: N,
+    ) -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let start = builder.start();
+        let short = builder.add_node(AddNode::new("short", 1));
+        let long_1 = builder.add_node(AddNode::new("long_1", 2));
+        let long_2 = builder.add_node(long_2);
+        let join = builder.add_node(AddNode::new("join", 10));
+        builder.add_edge(start, short, "short".to_string());
+        builder.add_edge(start, long_1, "long".to_string());
+        builder.add_edge(long_1, long_2, "long_1 -> long_2".to_string());
+        builder.add_join(&[short, long_2], join, "join".to_string());
+        builder.add_edge(join, builder.end(), "end".to_string());
+        builder.compile().unwrap().with_checkpointer(saver)
+    }
+
+    #[tokio::test]
+    async fn test_run_resumes_join_barrier() {
+        let saver = Arc::new(MemorySaver::new());
+        let config = RunConfig::default().with_thread_id("thread-1");
+
+        let graph = join_graph(saver.clone(), FailNode);
+        assert!(
+            graph
+                .run_with_config(CounterState::default(), &config)
+                .await
+                .is_err()
+        );
+        let checkpoint = saver.get("thread-1").await.unwrap().unwrap();
+        assert_eq!(checkpoint.barriers["join"], vec!["short"]);
+
+        let graph = join_graph(saver.clone(), AddNode::new("fail", 4));
+        let state = graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+        assert_eq!(state.visited, vec!["short", "long_1", "fail", "join"]);
+        assert_eq!(state.count, 17);
+    }
+
+    /// `a -> approve -> b` that pauses before or after `approve`.
+    fn approval_graph(saver: Arc<MemorySaver>, interrupt: Interrupt) -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let approve = builder.add_node(AddNode::new("approve", 10));
+        let b = builder.add_node(AddNode::new("b", 100));
+        builder.add_edge(a, approve, "a -> approve".to_string());
+        builder.add_edge(approve, b, "approve -> b".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        match interrupt {
+            Interrupt::Before => builder.interrupt_before(approve),
+            Interrupt::After => builder.interrupt_after(approve),
+        }
+        builder.compile().unwrap().with_checkpointer(saver)
+    }
+
+    #[tokio::test]
+    async fn test_run_interrupt_before() {
+        let saver = Arc::new(MemorySaver::new());
+        let graph = approval_graph(saver.clone(), Interrupt::Before);
+
+        let config = RunConfig::default().with_thread_id("thread-1");
+        let (mut state, handle) = match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            Err(GraphError::Interrupted { state, handle }) => (state, handle),
+            _ => panic!("Expected Interrupted"),
+        };
+        assert_eq!(state.visited, vec!["a"]);
+        assert_eq!(handle.nodes, vec!["approve"]);
+        assert_eq!(handle.interrupt, Interrupt::Before);
+
+        // 別のプロセスで、シリアライズしたハンドルから再開する
+        let handle: ResumeHandle =
+            serde_json::from_str(&serde_json::to_string(&handle).unwrap()).unwrap();
+        let graph = approval_graph(saver.clone(), Interrupt::Before);
+        state.count = 1000;
+        let state = graph.resume(&handle, state).await.unwrap();
+        assert_eq!(state.visited, vec!["a", "approve", "b"]);
+        assert_eq!(state.count, 1110);
+
+        // 使用済みのハンドルでは再開できない
+        match graph.resume(&handle, CounterState::default()).await {
+            Err(GraphError::InvalidResumeHandle { thread_id }) => {
+                assert_eq!(thread_id, "thread-1");
+            }
+            _ => panic!("Expected InvalidResumeHandle"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_interrupt_after() {
+        let saver = Arc::new(MemorySaver::new());
+        let graph = approval_graph(saver.clone(), Interrupt::After);
+
+        let config = RunConfig::default().with_thread_id("thread-1");
+        let (state, handle) = match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            Err(GraphError::Interrupted { state, handle }) => (state, handle),
+            _ => panic!("Expected Interrupted"),
+        };
+        assert_eq!(state.visited, vec!["a", "approve"]);
+        assert_eq!(handle.interrupt, Interrupt::After);
+        assert_eq!(handle.step, 2);
+
+        let state = graph.resume(&handle, state).await.unwrap();
+        assert_eq!(state.visited, vec!["a", "approve", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_interrupt_without_checkpointer() {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        builder.interrupt_before(b);
+        let graph = builder.compile().unwrap();
+
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::CheckpointerNotSet { node }) => assert_eq!(node, "b"),
+            _ => panic!("Expected CheckpointerNotSet"),
+        }
+    }
+
+    fn draw_graph() -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("say \"hi\"", 2));
+        let end = builder.end();
+        builder.add_edge(builder.start(), a, "start".to_string());
+        builder.add_edge(a, b, "retry".to_string());
+        builder.add_edge(a, end, "done".to_string());
+        builder.add_edge(b, a, "b -> a".to_string());
+        builder.add_conditional_edges(a, move |_: &CounterState| end);
+        builder.compile().unwrap()
+    }
+
+    fn history_graph(saver: Arc<MemorySaver>) -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let b = builder.add_node(AddNode::new("b", 10));
+        let c = builder.add_node(AddNode::new("c", 100));
+        builder.add_edge(a, b, "a -> b".to_string());
+        builder.add_edge(b, c, "b -> c".to_string());
+        builder.add_edge(c, builder.end(), "end".to_string());
+        builder.compile().unwrap().with_checkpointer(saver)
+    }
+
+    #[tokio::test]
+    async fn test_get_state_history() {
+        let saver = Arc::new(MemorySaver::new());
+        let graph = history_graph(saver.clone());
+        let config = RunConfig::default().with_thread_id("thread-1");
+        graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+
+        let history = graph.get_state_history("thread-1").await.unwrap();
+        let steps: Vec<_> = history
+            .iter()
+            .map(|snapshot| {
+                (
+                    snapshot.step,
+                    snapshot.completed_nodes.clone(),
+                    snapshot.next_nodes.clone(),
+                    snapshot.state.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (0, vec![], vec!["a".to_string()], 0),
+                (1, vec!["a".to_string()], vec!["b".to_string()], 1),
+                (2, vec!["b".to_string()], vec!["c".to_string()], 11),
+                (3, vec!["c".to_string()], vec![], 111),
+            ]
+        );
+        assert!(graph.get_state_history("other").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fork() {
+        let saver = Arc::new(MemorySaver::new());
+        let graph = history_graph(saver.clone());
+        let config = RunConfig::default().with_thread_id("thread-1");
+        graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+
+        // ステップ1 (a の実行後) の状態を書き換えて、b から実行し直す
+        let mut state = graph.get_state_history("thread-1").await.unwrap()[1]
+            .state
+            .clone();
+        state.count = 5;
+        let snapshot = graph.fork("thread-1", 1, state).await.unwrap();
+        assert_eq!(snapshot.next_nodes, vec!["b"]);
+
+        let state = graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+            .unwrap();
+        assert_eq!(state.count, 115);
+        assert_eq!(state.visited, vec!["a", "b", "c"]);
+
+        // 元の実行の履歴も残っている
+        let history = graph.get_state_history("thread-1").await.unwrap();
+        assert_eq!(history.len(), 7);
+        assert_eq!(history[3].state.count, 111);
+        assert_eq!(history.last().unwrap().state.count, 115);
+
+        match graph.fork("thread-1", 10, CounterState::default()).await {
+            Err(GraphError::CheckpointNotFound { thread_id, step }) => {
+                assert_eq!((thread_id.as_str(), step), ("thread-1", 10))
+            }
+            _ => panic!("Expected CheckpointNotFound"),
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let expected = r#"digraph {
+    0 [label="__start__" shape=ellipse];
+    1 [label="__end__" shape=ellipse];
+    2 [label="a" shape=box];
+    3 [label="say \"hi\"" shape=box];
+    0 -> 2 [label="start"];
+    2 -> 3 [label="retry" style=dashed];
+    2 -> 1 [label="done" style=dashed];
+    3 -> 2 [label="b -> a"];
+}
+"#;
+        assert_eq!(draw_graph().to_dot(), expected);
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let expected = r#"graph TD
+    n0(["__start__"])
+    n1(["__end__"])
+    n2["a"]
+    n3["say #quot;hi#quot;"]
+    n0 -->|"start"| n2
+    n2 -.->|"retry"| n3
+    n2 -.->|"done"| n1
+    n3 -->|"b -> a"| n2
+"#;
+        assert_eq!(draw_graph().to_mermaid(), expected);
+    }
+
+    struct TokenNode;
+
+    #[async_trait]
+    impl FunNode<CounterState> for TokenNode {
+        fn get_name(&self) -> String {
+            "llm".to_string()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            // LLM::invoke_stream と同じようにトークンを流す
+            crate::llm::emit_token("Hel");
+            crate::llm::emit_token("lo");
+            Ok(CounterStateUpdate {
+                count: Some(1),
+                visited: Some(vec!["llm".to_string()]),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let mut builder = FunGraphBuilder::new();
+        let llm = builder.add_node(TokenNode);
+        let b = builder.add_node(AddNode::new("b", 2));
+        builder.add_edge(llm, b, "llm -> b".to_string());
+        builder.add_edge(b, builder.end(), "end".to_string());
+        let graph = builder.compile().unwrap();
+
+        let events: Vec<String> = graph
+            .stream(CounterState::default())
+            .map(|event| match event {
+                GraphEvent::NodeStarted { node, step } => format!("started {} {}", node, step),
+                GraphEvent::NodeFinished { node, state, .. } => {
+                    format!("finished {} {}", node, state.count)
+                }
+                GraphEvent::EdgeTaken { from, to } => format!("edge {} -> {}", from, to),
+                GraphEvent::Token { node, token } => format!("token {} {}", node, token),
+                GraphEvent::RunFinished { result } => {
+                    format!("run finished {}", result.unwrap().count)
+                }
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![
+                "edge __start__ -> llm",
+                "started llm 0",
+                "token llm Hel",
+                "token llm lo",
+                "finished llm 1",
+                "edge llm -> b",
+                "started b 1",
+                "finished b 3",
+                "edge b -> __end__",
+                "run finished 3",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_node_error() {
+        let graph = fail_graph(FailNode);
+        let events: Vec<GraphEvent<CounterState>> =
+            graph.stream(CounterState::default()).collect().await;
+        assert!(matches!(
+            events.last(),
+            Some(GraphEvent::RunFinished {
+                result: Err(GraphError::NodeError { .. })
+            })
+        ));
+    }
+
+    /// Fails `failures` times before it succeeds.
+    struct FlakyNode {
+        failures: usize,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FunNode<CounterState> for FlakyNode {
+        fn get_name(&self) -> String {
+            "flaky".to_string()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(NodeError::Timeout(Duration::from_secs(1)));
+            }
+            Ok(CounterStateUpdate {
+                count: Some(1),
+                visited: Some(vec!["flaky".to_string()]),
+            })
+        }
+    }
+
+    fn single_node_graph<N: FunNode<CounterState> + 'static>(
+        node: N,
+        policy: Option<RetryPolicy>,
+        timeout: Option<Duration>,
+    ) -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let node = builder.add_node(node);
+        builder.add_edge(node, builder.end(), "end".to_string());
+        if let Some(policy) = policy {
+            builder.set_retry_policy(node, policy);
+        }
+        if let Some(timeout) = timeout {
+            builder.set_timeout(node, timeout);
+        }
+        builder.compile().unwrap()
+    }
+
+    fn fast_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+            .with_max_attempts(3)
+            .with_initial_interval(Duration::from_millis(1))
+    }
+
+    #[tokio::test]
+    async fn test_run_retry() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node = FlakyNode {
+            failures: 2,
+            calls: calls.clone(),
+        };
+        let graph = single_node_graph(node, Some(fast_retry_policy()), None);
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(state.visited, vec!["flaky"]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_retry_exhausted() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node = FlakyNode {
+            failures: 5,
+            calls: calls.clone(),
+        };
+        let graph = single_node_graph(node, Some(fast_retry_policy()), None);
+
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::RetryExhausted {
+                node,
+                attempts,
+                source,
+                ..
+            }) => {
+                assert_eq!(node, "flaky");
+                assert_eq!(attempts, 3);
+                assert!(matches!(source, NodeError::Timeout(_)));
+            }
+            _ => panic!("Expected RetryExhausted"),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    /// Fails with a timeout first and with a permanent error afterwards.
+    struct TransientThenPermanentNode {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FunNode<CounterState> for TransientThenPermanentNode {
+        fn get_name(&self) -> String {
+            "transient".to_string()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(NodeError::Timeout(Duration::from_secs(1)));
+            }
+            Err(NodeError::OtherError("invalid input".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_retry_permanent_error_after_transient() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node = TransientThenPermanentNode {
+            calls: calls.clone(),
+        };
+        let graph = single_node_graph(node, Some(fast_retry_policy()), None);
+
+        // リトライ回数を使い切る前に再試行できないエラーで止まったので、RetryExhausted ではない
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::NodeError { node, source, .. }) => {
+                assert_eq!(node, "transient");
+                assert!(matches!(source, NodeError::OtherError(_)));
+            }
+            _ => panic!("Expected NodeError"),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_retry_not_retryable() {
+        let graph = single_node_graph(FailNode, Some(fast_retry_policy()), None);
+
+        assert!(matches!(
+            graph.run(CounterState::default()).await,
+            Err(GraphError::NodeError { .. })
+        ));
+    }
+
+    struct SlowNode;
+
+    #[async_trait]
+    impl FunNode<CounterState> for SlowNode {
+        fn get_name(&self) -> String {
+            "slow".to_string()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(CounterStateUpdate::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout() {
+        let graph = single_node_graph(SlowNode, None, Some(Duration::from_millis(10)));
+
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::NodeError { node, source, .. }) => {
+                assert_eq!(node, "slow");
+                assert_eq!(source.to_string(), "Node timed out after 10ms");
+            }
+            _ => panic!("Expected NodeError"),
+        }
+    }
+
+    struct PanicNode;
+
+    #[async_trait]
+    impl FunNode<CounterState> for PanicNode {
+        fn get_name(&self) -> String {
+            "panic".to_string()
+        }
+
+        async fn run(&self, _state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            panic!("boom");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_node_panic() {
+        let graph = single_node_graph(PanicNode, None, None);
+
+        match graph.run(CounterState::default()).await {
+            Err(GraphError::NodeError { source, .. }) => {
+                assert_eq!(source.to_string(), "Node panicked: boom");
+            }
+            _ => panic!("Expected NodeError"),
+        }
+    }
+
+    /// Adds ten times the count of its own state.
+    struct SummarizeNode;
+
+    #[async_trait]
+    impl FunNode<CounterState> for SummarizeNode {
+        fn get_name(&self) -> String {
+            "summarize".to_string()
+        }
+
+        async fn run(&self, state: CounterState) -> Result<CounterStateUpdate, NodeError> {
+            Ok(CounterStateUpdate {
+                count: Some(state.count * 10),
+                visited: Some(vec![format!("summarize {}", state.count)]),
+            })
+        }
+    }
+
+    /// `split` sends one `summarize` per document, then `reduce` runs once.
+    fn map_reduce_builder(documents: i32) -> (FunGraphBuilder<CounterState>, NodeIndex) {
+        let mut builder = FunGraphBuilder::new();
+        let split = builder.add_node(AddNode::new("split", 0));
+        let summarize = builder.add_node(SummarizeNode);
+        let reduce = builder.add_node(AddNode::new("reduce", 0));
+        builder.add_edge(split, summarize, "documents".to_string());
+        builder.add_edge(summarize, reduce, "summaries".to_string());
+        builder.add_edge(reduce, builder.end(), "end".to_string());
+        builder.add_conditional_edges(split, move |_: &CounterState| {
+            let sends = (1..=documents)
+                .map(|count| {
+                    let state = CounterState {
+                        count,
+                        visited: vec![],
+                    };
+                    (summarize, state)
+                })
+                .collect();
+            Route::Send(sends)
+        });
+        (builder, summarize)
+    }
+
+    #[tokio::test]
+    async fn test_run_send() {
+        let (builder, _) = map_reduce_builder(3);
+        let graph = builder.compile().unwrap();
+
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(
+            state.visited,
+            vec![
+                "split",
+                "summarize 1",
+                "summarize 2",
+                "summarize 3",
+                "reduce"
+            ]
+        );
+        assert_eq!(state.count, 60);
+
+        // 送り先がなければ、その分岐はそこで終わる
+        let (builder, _) = map_reduce_builder(0);
+        let graph = builder.compile().unwrap();
+        let state = graph.run(CounterState::default()).await.unwrap();
+        assert_eq!(state.visited, vec!["split"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_send_resumes_from_checkpoint() {
+        let saver = Arc::new(MemorySaver::new());
+        let (mut builder, summarize) = map_reduce_builder(2);
+        builder.interrupt_before(summarize);
+        let graph = builder.compile().unwrap().with_checkpointer(saver.clone());
+
+        let config = RunConfig::default().with_thread_id("thread-1");
+        let (state, handle) = match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            Err(GraphError::Interrupted { state, handle }) => (state, handle),
+            _ => panic!("Expected Interrupted"),
+        };
+        let checkpoint = saver.get("thread-1").await.unwrap().unwrap();
+        assert!(checkpoint.next_nodes.is_empty());
+        assert_eq!(checkpoint.sends.len(), 2);
+        assert_eq!(checkpoint.sends[1].0, "summarize");
+        assert_eq!(checkpoint.sends[1].1["count"], 2);
+
+        let state = graph.resume(&handle, state).await.unwrap();
+        assert_eq!(
+            state.visited,
+            vec!["split", "summarize 1", "summarize 2", "reduce"]
+        );
+    }
+
+    /// `a -> slow`
+    fn slow_graph() -> FunGraph<CounterState> {
+        let mut builder = FunGraphBuilder::new();
+        let a = builder.add_node(AddNode::new("a", 1));
+        let slow = builder.add_node(SlowNode);
+        builder.add_edge(a, slow, "a -> slow".to_string());
+        builder.add_edge(slow, builder.end(), "end".to_string());
+        builder.compile().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_cancelled() {
+        let graph = slow_graph();
+        let token = CancellationToken::new();
+        let config = RunConfig::default().with_cancellation_token(token.clone());
+
+        let cancel = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        });
+        match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            // slow の実行中に止まり、a までの状態が返る
+            Err(GraphError::Cancelled { state }) => assert_eq!(state.visited, vec!["a"]),
+            _ => panic!("Expected Cancelled"),
+        }
+        cancel.await.unwrap();
+
+        // キャンセル済みなら何も実行しない
+        match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            Err(GraphError::Cancelled { state }) => assert!(state.visited.is_empty()),
+            _ => panic!("Expected Cancelled"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_deadline_exceeded() {
+        let graph = slow_graph();
+        let config = RunConfig::default().with_timeout(Duration::from_millis(20));
+
+        match graph
+            .run_with_config(CounterState::default(), &config)
+            .await
+        {
+            Err(GraphError::DeadlineExceeded { state }) => assert_eq!(state.visited, vec!["a"]),
+            _ => panic!("Expected DeadlineExceeded"),
+        }
     }
 }
@@ -0,0 +1,87 @@
+use std::ops::AddAssign;
+
+/// Decides how an update of a state field is merged into its current value.
+///
+/// # Usage
+/// ```rust,ignore
+/// impl FunState for ChatState {
+///     type Update = ChatStateUpdate;
+///
+///     fn apply(&mut self, update: ChatStateUpdate) {
+///         Append::reduce_option(&mut self.messages, update.messages);
+///         Sum::reduce_option(&mut self.turns, update.turns);
+///     }
+/// }
+/// ```
+pub trait Reducer<T> {
+    fn reduce(current: &mut T, update: T);
+
+    /// Reduces `update` only if the node has set it.
+    fn reduce_option(current: &mut T, update: Option<T>) {
+        if let Some(update) = update {
+            Self::reduce(current, update);
+        }
+    }
+}
+
+/// Replaces the current value with the update.
+pub struct LastWriteWins;
+
+impl<T> Reducer<T> for LastWriteWins {
+    fn reduce(current: &mut T, update: T) {
+        *current = update;
+    }
+}
+
+/// Appends the updated items to the current list, e.g. for message histories.
+pub struct Append;
+
+impl<T> Reducer<Vec<T>> for Append {
+    fn reduce(current: &mut Vec<T>, update: Vec<T>) {
+        current.extend(update);
+    }
+}
+
+/// Adds the update to the current value, e.g. for counters.
+pub struct Sum;
+
+impl<T: AddAssign> Reducer<T> for Sum {
+    fn reduce(current: &mut T, update: T) {
+        *current += update;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_write_wins() {
+        let mut value = "old".to_string();
+        LastWriteWins::reduce(&mut value, "new".to_string());
+        assert_eq!(value, "new");
+    }
+
+    #[test]
+    fn test_append() {
+        let mut value = vec![1, 2];
+        Append::reduce(&mut value, vec![3]);
+        assert_eq!(value, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sum() {
+        let mut value = 1;
+        Sum::reduce(&mut value, 2);
+        assert_eq!(value, 3);
+    }
+
+    #[test]
+    fn test_reduce_option() {
+        let mut value = vec![1];
+        Append::reduce_option(&mut value, None);
+        assert_eq!(value, vec![1]);
+        Append::reduce_option(&mut value, Some(vec![2]));
+        assert_eq!(value, vec![1, 2]);
+    }
+}
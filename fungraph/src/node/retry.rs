@@ -0,0 +1,131 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use super::NodeError;
+
+/// How often and how fast a failed node is run again.
+///
+/// The n-th retry waits `initial_interval * backoff_factor^(n - 1)`, capped at `max_interval`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let policy = RetryPolicy::default()
+///     .with_max_attempts(5)
+///     .with_initial_interval(Duration::from_secs(1))
+///     .with_retry_on(|error: &NodeError| matches!(error, NodeError::LLMError(_)));
+/// builder.set_retry_policy(llm, policy);
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    initial_interval: Duration,
+    backoff_factor: f64,
+    max_interval: Duration,
+    retry_on: Arc<dyn Fn(&NodeError) -> bool + Send + Sync>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_interval: Duration::from_millis(500),
+            backoff_factor: 2.0,
+            max_interval: Duration::from_secs(30),
+            retry_on: Arc::new(NodeError::is_transient),
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_interval", &self.initial_interval)
+            .field("backoff_factor", &self.backoff_factor)
+            .field("max_interval", &self.max_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RetryPolicy {
+    /// Number of runs including the first one.
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Whether the policy retries `error`, regardless of the attempts left.
+    pub fn is_retryable(&self, error: &NodeError) -> bool {
+        (self.retry_on)(error)
+    }
+
+    /// Whether `error` of the `attempt`-th run (1-based) is run again.
+    pub fn should_retry(&self, attempt: usize, error: &NodeError) -> bool {
+        attempt < self.max_attempts && self.is_retryable(error)
+    }
+
+    /// Wait before the run after the `attempt`-th failed run (1-based).
+    pub fn interval(&self, attempt: usize) -> Duration {
+        let factor = self.backoff_factor.powi(attempt.saturating_sub(1) as i32);
+        self.initial_interval.mul_f64(factor).min(self.max_interval)
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_initial_interval(mut self, initial_interval: Duration) -> Self {
+        self.initial_interval = initial_interval;
+        self
+    }
+
+    pub fn with_backoff_factor(mut self, backoff_factor: f64) -> Self {
+        self.backoff_factor = backoff_factor;
+        self
+    }
+
+    pub fn with_max_interval(mut self, max_interval: Duration) -> Self {
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Chooses the retryable errors. By default `NodeError::is_transient` errors are retried.
+    pub fn with_retry_on<F>(mut self, retry_on: F) -> Self
+    where
+        F: Fn(&NodeError) -> bool + Send + Sync + 'static,
+    {
+        self.retry_on = Arc::new(retry_on);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_interval() {
+        let policy = RetryPolicy::default()
+            .with_initial_interval(Duration::from_secs(1))
+            .with_backoff_factor(2.0)
+            .with_max_interval(Duration::from_secs(5));
+        assert_eq!(policy.interval(1), Duration::from_secs(1));
+        assert_eq!(policy.interval(2), Duration::from_secs(2));
+        assert_eq!(policy.interval(3), Duration::from_secs(4));
+        assert_eq!(policy.interval(4), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_retry_policy_should_retry() {
+        let policy = RetryPolicy::default().with_max_attempts(2);
+        let timeout = NodeError::Timeout(Duration::from_secs(1));
+        let other = NodeError::OtherError("invalid input".to_string());
+        assert!(policy.should_retry(1, &timeout));
+        assert!(!policy.should_retry(2, &timeout));
+        assert!(!policy.should_retry(1, &other));
+        assert!(policy.is_retryable(&timeout));
+        assert!(!policy.is_retryable(&other));
+
+        let policy = policy.with_retry_on(|_| true);
+        assert!(policy.should_retry(1, &other));
+    }
+}
@@ -0,0 +1,362 @@
+use async_trait::async_trait;
+
+use super::{
+    FunGraph, FunNode, FunState, GraphError, NodeError, RunConfig, config::node_run_config,
+};
+
+/// Runs a whole `FunGraph` as a single node of a larger graph.
+///
+/// The inner graph may have its own state type `T`.
+/// `input` builds the inner state from the outer state,
+/// and `output` turns the final inner state into an update of the outer state.
+/// Inner runs use `RunConfig::default()` and are not checkpointed.
+/// They also stop with the cancellation token and the deadline of the outer run.
+/// A failed inner run returns `NodeError::SubGraphError` with the error of the inner node as its source,
+/// so a `RetryPolicy` of the subgraph node retries transient inner errors.
+/// An interrupted inner run returns `NodeError::SubGraphInterrupted`.
+///
+/// # Usage
+/// ```rust,ignore
+/// let research = SubGraph::new(
+///     "research",
+///     research_graph,
+///     |state: &ChatState| ResearchState::new(&state.question),
+///     |research: ResearchState| ChatStateUpdate::new().with_notes(research.notes),
+/// );
+/// let research = builder.add_node(research);
+/// ```
+pub struct SubGraph<S: FunState, T: FunState> {
+    name: String,
+    graph: FunGraph<T>,
+    config: RunConfig,
+    input: Box<dyn Fn(&S) -> T + Send + Sync>,
+    output: Box<dyn Fn(T) -> S::Update + Send + Sync>,
+}
+
+impl<S, T> SubGraph<S, T>
+where
+    S: FunState,
+    T: FunState,
+{
+    pub fn new<I, O>(name: &str, graph: FunGraph<T>, input: I, output: O) -> Self
+    where
+        I: Fn(&S) -> T + Send + Sync + 'static,
+        O: Fn(T) -> S::Update + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            graph,
+            config: RunConfig::default(),
+            input: Box::new(input),
+            output: Box::new(output),
+        }
+    }
+
+    pub fn with_config(mut self, config: RunConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn to_node_error(&self, error: GraphError<T>) -> NodeError {
+        let graph = self.name.clone();
+        let message = error.to_string();
+        match error {
+            GraphError::Interrupted { handle, .. } => {
+                NodeError::SubGraphInterrupted { graph, handle }
+            }
+            GraphError::NodeError { source, .. } | GraphError::RetryExhausted { source, .. } => {
+                NodeError::SubGraphError {
+                    graph,
+                    message,
+                    source: Some(Box::new(source)),
+                }
+            }
+            _ => NodeError::SubGraphError {
+                graph,
+                message,
+                source: None,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl<S, T> FunNode<S> for SubGraph<S, T>
+where
+    S: FunState,
+    T: FunState,
+{
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn run(&self, state: S) -> Result<S::Update, NodeError> {
+        let inner_state = (self.input)(&state);
+        let config = match node_run_config() {
+            Some(outer) => self.config.clone().with_outer(outer),
+            None => self.config.clone(),
+        };
+        let inner_state = self
+            .graph
+            .run_with_config(inner_state, &config)
+            .await
+            .map_err(|e| self.to_node_error(e))?;
+        Ok((self.output)(inner_state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use serde::{Deserialize, Serialize};
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::node::{
+        Append, CancellationToken, FunGraphBuilder, MemorySaver, Reducer, RetryPolicy,
+        config::with_node_run_config,
+    };
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct ChatState {
+        question: String,
+        answers: Vec<String>,
+    }
+
+    impl FunState for ChatState {
+        type Update = Vec<String>;
+
+        fn apply(&mut self, update: Vec<String>) {
+            Append::reduce(&mut self.answers, update);
+        }
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct ResearchState {
+        query: String,
+        notes: Vec<String>,
+    }
+
+    impl FunState for ResearchState {
+        type Update = Vec<String>;
+
+        fn apply(&mut self, update: Vec<String>) {
+            Append::reduce(&mut self.notes, update);
+        }
+    }
+
+    struct SearchNode {
+        name: &'static str,
+    }
+
+    #[async_trait]
+    impl FunNode<ResearchState> for SearchNode {
+        fn get_name(&self) -> String {
+            self.name.to_string()
+        }
+
+        async fn run(&self, state: ResearchState) -> Result<Vec<String>, NodeError> {
+            if state.query.is_empty() {
+                return Err(NodeError::OtherError("empty query".to_string()));
+            }
+            Ok(vec![format!("{}: {}", self.name, state.query)])
+        }
+    }
+
+    /// Times out on the first call.
+    struct FlakyNode {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl FunNode<ResearchState> for FlakyNode {
+        fn get_name(&self) -> String {
+            "flaky".to_string()
+        }
+
+        async fn run(&self, state: ResearchState) -> Result<Vec<String>, NodeError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(NodeError::Timeout(Duration::from_secs(1)));
+            }
+            Ok(vec![format!("flaky: {}", state.query)])
+        }
+    }
+
+    fn research_graph() -> FunGraph<ResearchState> {
+        let mut builder = FunGraphBuilder::new();
+        let web = builder.add_node(SearchNode { name: "web" });
+        let docs = builder.add_node(SearchNode { name: "docs" });
+        builder.add_edge(web, docs, "web -> docs".to_string());
+        builder.add_edge(docs, builder.end(), "end".to_string());
+        builder.compile().unwrap()
+    }
+
+    fn chat_graph() -> FunGraph<ChatState> {
+        let mut builder = FunGraphBuilder::new();
+        let research = builder.add_node(SubGraph::new(
+            "research",
+            research_graph(),
+            |state: &ChatState| ResearchState {
+                query: state.question.clone(),
+                notes: vec![],
+            },
+            |research: ResearchState| research.notes,
+        ));
+        builder.add_edge(research, builder.end(), "end".to_string());
+        builder.compile().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_run() {
+        let state = ChatState {
+            question: "rust".to_string(),
+            answers: vec![],
+        };
+        let state = chat_graph().run(state).await.unwrap();
+        assert_eq!(state.answers, vec!["web: rust", "docs: rust"]);
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_error() {
+        match chat_graph().run(ChatState::default()).await {
+            Err(GraphError::NodeError { node, source, .. }) => {
+                assert_eq!(node, "research");
+                assert_eq!(
+                    source.to_string(),
+                    "Subgraph 'research' failed: Node 'web' failed: Error: empty query"
+                );
+                assert_eq!(source.source().unwrap().to_string(), "Error: empty query");
+                assert!(matches!(
+                    source,
+                    NodeError::SubGraphError { source: Some(inner), .. }
+                        if matches!(*inner, NodeError::OtherError(_))
+                ));
+            }
+            _ => panic!("Expected NodeError"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_interrupted() {
+        let mut research = FunGraphBuilder::new();
+        let web = research.add_node(SearchNode { name: "web" });
+        let docs = research.add_node(SearchNode { name: "docs" });
+        research.add_edge(web, docs, "web -> docs".to_string());
+        research.add_edge(docs, research.end(), "end".to_string());
+        research.interrupt_before(docs);
+        let research = research
+            .compile()
+            .unwrap()
+            .with_checkpointer(Arc::new(MemorySaver::new()));
+
+        let mut builder = FunGraphBuilder::new();
+        let node = builder.add_node(
+            SubGraph::new(
+                "research",
+                research,
+                |state: &ChatState| ResearchState {
+                    query: state.question.clone(),
+                    notes: vec![],
+                },
+                |research: ResearchState| research.notes,
+            )
+            .with_config(RunConfig::default().with_thread_id("research-1")),
+        );
+        builder.add_edge(node, builder.end(), "end".to_string());
+        let graph = builder.compile().unwrap();
+        let state = ChatState {
+            question: "rust".to_string(),
+            answers: vec![],
+        };
+
+        match graph.run(state).await {
+            Err(GraphError::NodeError { node, source, .. }) => {
+                assert_eq!(node, "research");
+                match source {
+                    NodeError::SubGraphInterrupted { graph, handle } => {
+                        assert_eq!(graph, "research");
+                        assert_eq!(handle.thread_id, "research-1");
+                        assert_eq!(handle.nodes, vec!["docs"]);
+                    }
+                    other => panic!("Expected SubGraphInterrupted, got {:?}", other),
+                }
+            }
+            _ => panic!("Expected NodeError"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_retry_transient_inner_error() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut research = FunGraphBuilder::new();
+        let flaky = research.add_node(FlakyNode {
+            calls: calls.clone(),
+        });
+        research.add_edge(flaky, research.end(), "end".to_string());
+
+        let mut builder = FunGraphBuilder::new();
+        let node = builder.add_node(SubGraph::new(
+            "research",
+            research.compile().unwrap(),
+            |state: &ChatState| ResearchState {
+                query: state.question.clone(),
+                notes: vec![],
+            },
+            |research: ResearchState| research.notes,
+        ));
+        builder.add_edge(node, builder.end(), "end".to_string());
+        builder.set_retry_policy(
+            node,
+            RetryPolicy::default().with_initial_interval(Duration::from_millis(1)),
+        );
+        let state = ChatState {
+            question: "rust".to_string(),
+            answers: vec![],
+        };
+
+        let state = builder.compile().unwrap().run(state).await.unwrap();
+        assert_eq!(state.answers, vec!["flaky: rust"]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_stops_with_outer_run() {
+        let research = SubGraph::new(
+            "research",
+            research_graph(),
+            |state: &ChatState| ResearchState {
+                query: state.question.clone(),
+                notes: vec![],
+            },
+            |research: ResearchState| research.notes,
+        );
+        let state = ChatState {
+            question: "rust".to_string(),
+            answers: vec![],
+        };
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let outer = RunConfig::default().with_cancellation_token(token);
+        let result = with_node_run_config(outer, research.run(state.clone())).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Subgraph 'research' failed: Run was cancelled"
+        );
+
+        let outer = RunConfig::default().with_deadline(Instant::now());
+        let result = with_node_run_config(outer, research.run(state)).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Subgraph 'research' failed: Run exceeded its deadline"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::debug;
+
+use crate::{
+    llm::{Message, MessageType},
+    tools::Tool,
+    types::{TokenUsage, openai::ChatCompletionMessageToolCall},
+};
+
+use super::{FunNode, FunState, NodeError};
+
+/// State with a chat history, e.g. a field `messages: Vec<Message>` with the `Append` reducer.
+pub trait MessagesState: FunState {
+    fn messages(&self) -> &[Message];
+
+    /// Update that appends `messages` to the chat history.
+    fn append_messages(messages: Vec<Message>) -> Self::Update;
+
+    /// Adds the token usage of an LLM call to `update`, see `ChatModelNode`.
+    /// States that do not track token usage can keep the default, which drops it.
+    fn record_token_usage(update: Self::Update, _usage: TokenUsage) -> Self::Update {
+        update
+    }
+
+    /// Whether the last message is an AI message that asks for tool calls.
+    /// Use it in a conditional edge to decide between the `ToolNode` and `END`.
+    fn has_tool_calls(&self) -> bool {
+        self.messages()
+            .last()
+            .is_some_and(|message| !pending_tool_calls(message).is_empty())
+    }
+}
+
+fn pending_tool_calls(message: &Message) -> Vec<ChatCompletionMessageToolCall> {
+    if message.message_type != MessageType::AIMessage {
+        return vec![];
+    }
+    message
+        .tool_calls
+        .as_ref()
+        .and_then(|tool_calls| serde_json::from_value(tool_calls.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Runs the tool calls of the last AI message and appends their results as tool messages.
+/// The calls run concurrently, and the results keep the order of the calls.
+/// A failed or unknown tool is reported to the LLM in its tool message instead of failing the run,
+/// so that the LLM can retry with other arguments.
+///
+/// # Usage
+/// ```rust,ignore
+/// let agent = builder.add_node(AgentNode::new(llm));
+/// let tools = builder.add_node(ToolNode::new("tools", vec![Box::new(WeatherTool {})]));
+/// builder.add_edge(builder.start(), agent, "start".to_string());
+/// builder.add_edge(agent, tools, "tool call".to_string());
+/// builder.add_edge(agent, builder.end(), "answer".to_string());
+/// builder.add_edge(tools, agent, "tool result".to_string());
+/// builder.add_conditional_edges(agent, move |state: &ChatState| {
+///     if state.has_tool_calls() { tools } else { end }
+/// });
+/// ```
+pub struct ToolNode {
+    name: String,
+    tools: HashMap<String, Box<dyn Tool>>,
+}
+
+impl ToolNode {
+    pub fn new(name: &str, tools: Vec<Box<dyn Tool>>) -> Self {
+        Self {
+            name: name.to_string(),
+            tools: tools
+                .into_iter()
+                .map(|tool| (tool.name().to_string(), tool))
+                .collect(),
+        }
+    }
+
+    async fn call(&self, tool_call: &ChatCompletionMessageToolCall) -> Message {
+        let name = &tool_call.function.name;
+        let content = match self.tools.get(name) {
+            Some(tool) => Self::invoke(tool.as_ref(), tool_call)
+                .await
+                .unwrap_or_else(|e| format!("Error: tool '{}' failed: {}", name, e)),
+            None => format!("Error: tool '{}' is not found", name),
+        };
+        debug!("ToolNode: {} -> {}", name, content);
+        Message::new_tool_message(content, tool_call.id.clone())
+    }
+
+    async fn invoke(
+        tool: &dyn Tool,
+        tool_call: &ChatCompletionMessageToolCall,
+    ) -> anyhow::Result<String> {
+        let arguments = serde_json::from_str(&tool_call.function.arguments)?;
+        tool.call(&arguments).await
+    }
+}
+
+#[async_trait]
+impl<S: MessagesState> FunNode<S> for ToolNode {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn run(&self, state: S) -> Result<S::Update, NodeError> {
+        let tool_calls = state
+            .messages()
+            .last()
+            .map(pending_tool_calls)
+            .unwrap_or_default();
+        let messages = join_all(tool_calls.iter().map(|tool_call| self.call(tool_call))).await;
+        Ok(S::append_messages(messages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        node::{Append, FunGraphBuilder, Reducer},
+        types::openai::{Parameters, Property},
+    };
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct ChatState {
+        messages: Vec<Message>,
+    }
+
+    impl FunState for ChatState {
+        type Update = Vec<Message>;
+
+        fn apply(&mut self, update: Vec<Message>) {
+            Append::reduce(&mut self.messages, update);
+        }
+    }
+
+    impl MessagesState for ChatState {
+        fn messages(&self) -> &[Message] {
+            &self.messages
+        }
+
+        fn append_messages(messages: Vec<Message>) -> Vec<Message> {
+            messages
+        }
+    }
+
+    struct WeatherTool;
+
+    #[async_trait]
+    impl Tool for WeatherTool {
+        fn name(&self) -> &'static str {
+            "get_weather"
+        }
+
+        fn description(&self) -> &'static str {
+            "Get the current weather in a given location"
+        }
+
+        fn parameters(&self) -> Parameters {
+            Parameters {
+                r#type: "object".to_string(),
+                properties: HashMap::from([(
+                    "location".to_string(),
+                    Property {
+                        r#type: "string".to_string(),
+                        description: None,
+                        enum_values: None,
+                    },
+                )]),
+                required: vec!["location".to_string()],
+            }
+        }
+
+        async fn call(&self, input: &Value) -> anyhow::Result<String> {
+            match input["location"].as_str() {
+                Some(location) => Ok(format!("sunny in {}", location)),
+                None => Err(anyhow::anyhow!("location is required")),
+            }
+        }
+    }
+
+    fn tool_call(id: &str, name: &str, arguments: &str) -> Value {
+        json!({
+            "id": id,
+            "type": "function",
+            "function": { "name": name, "arguments": arguments },
+        })
+    }
+
+    fn tool_call_message(tool_calls: Vec<Value>) -> Message {
+        Message::new_ai_message("").with_tool_calls(Value::Array(tool_calls))
+    }
+
+    #[tokio::test]
+    async fn test_tool_node() {
+        let node = ToolNode::new("tools", vec![Box::new(WeatherTool)]);
+        let state = ChatState {
+            messages: vec![
+                Message::new_human_message("weather?"),
+                tool_call_message(vec![
+                    tool_call("call_1", "get_weather", r#"{"location": "tokyo"}"#),
+                    tool_call("call_2", "get_weather", "{}"),
+                    tool_call("call_3", "get_time", "{}"),
+                ]),
+            ],
+        };
+        assert!(state.has_tool_calls());
+
+        let messages = node.run(state).await.unwrap();
+        let contents: Vec<_> = messages
+            .iter()
+            .map(|message| {
+                assert_eq!(message.message_type, MessageType::ToolMessage);
+                (
+                    message.id.clone().unwrap(),
+                    message.content.clone().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                ("call_1".to_string(), "sunny in tokyo".to_string()),
+                (
+                    "call_2".to_string(),
+                    "Error: tool 'get_weather' failed: location is required".to_string()
+                ),
+                (
+                    "call_3".to_string(),
+                    "Error: tool 'get_time' is not found".to_string()
+                ),
+            ]
+        );
+    }
+
+    /// Asks for the weather once, then answers with the tool result.
+    struct AgentNode;
+
+    #[async_trait]
+    impl FunNode<ChatState> for AgentNode {
+        fn get_name(&self) -> String {
+            "agent".to_string()
+        }
+
+        async fn run(&self, state: ChatState) -> Result<Vec<Message>, NodeError> {
+            let last = state.messages.last().unwrap();
+            if last.message_type == MessageType::ToolMessage {
+                let answer = format!("It is {}.", last.content.clone().unwrap());
+                return Ok(vec![Message::new_ai_message(answer)]);
+            }
+            Ok(vec![tool_call_message(vec![tool_call(
+                "call_1",
+                "get_weather",
+                r#"{"location": "osaka"}"#,
+            )])])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_node_in_graph() {
+        let mut builder = FunGraphBuilder::new();
+        let agent = builder.add_node(AgentNode);
+        let tools = builder.add_node(ToolNode::new("tools", vec![Box::new(WeatherTool)]));
+        let end = builder.end();
+        builder.add_edge(builder.start(), agent, "start".to_string());
+        builder.add_edge(agent, tools, "tool call".to_string());
+        builder.add_edge(agent, end, "answer".to_string());
+        builder.add_edge(tools, agent, "tool result".to_string());
+        builder.add_conditional_edges(
+            agent,
+            move |state: &ChatState| {
+                if state.has_tool_calls() { tools } else { end }
+            },
+        );
+        let graph = builder.compile().unwrap();
+
+        let state = ChatState {
+            messages: vec![Message::new_human_message("weather in osaka?")],
+        };
+        let state = graph.run(state).await.unwrap();
+        assert_eq!(state.messages.len(), 4);
+        assert!(!state.has_tool_calls());
+        assert_eq!(
+            state.messages.last().unwrap().content.as_deref(),
+            Some("It is sunny in osaka.")
+        );
+    }
+}
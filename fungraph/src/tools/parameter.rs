@@ -42,8 +42,8 @@ mod tests {
 
     #[test]
     fn test_parameters_json() {
-        let tool_param = TestToolParam {};
-        let json = serde_json::to_value(&TestToolParam::parameters()).unwrap();
+        let _tool_param = TestToolParam {};
+        let json = serde_json::to_value(TestToolParam::parameters()).unwrap();
         let json_value = json!(
             {
                 "type":"object",
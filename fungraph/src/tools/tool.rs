@@ -1,13 +1,12 @@
 use crate::types::openai::Parameters;
 
-use super::ToolParameters;
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::Value;
 use std::string::String;
 
 #[async_trait]
-pub trait Tool {
+pub trait Tool: Send + Sync {
     fn name(&self) -> &'static str;
     fn description(&self) -> &'static str;
     fn parameters(&self) -> Parameters;
@@ -25,6 +24,7 @@ pub trait Tool {
     }
 }
 
+#[allow(dead_code)]
 fn to_openai_tool<T: Tool>(tool: &T) -> crate::types::openai::Tool {
     crate::types::openai::Tool {
         r#type: crate::types::openai::ToolType::Function,
@@ -39,11 +39,13 @@ fn to_openai_tool<T: Tool>(tool: &T) -> crate::types::openai::Tool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::ToolParameters;
     use crate::types::openai::{Parameters, Property};
     use serde_json::json;
     use std::collections::HashMap;
 
     struct MyTool;
+    #[allow(dead_code)]
     struct MyToolParameters {
         name: String,
     }
@@ -86,7 +88,7 @@ mod tests {
         fn parameters(&self) -> Parameters {
             MyToolParameters::parameters()
         }
-        async fn call(&self, input: &Value) -> Result<String> {
+        async fn call(&self, _input: &Value) -> Result<String> {
             Ok("test".into())
         }
     }
@@ -129,6 +131,6 @@ mod tests {
 
     #[test]
     fn test_tool_runner() {
-        let my_tool = MyTool {};
+        let _my_tool = MyTool {};
     }
 }
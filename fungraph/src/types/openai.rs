@@ -3,7 +3,9 @@ use std::{collections::HashMap, pin::Pin};
 use futures::Stream;
 use serde::{Deserialize, Serialize};
 
-use crate::llm::{GenerateResult, LLMError};
+use serde_json::Value;
+
+use crate::llm::{GenerateResult, LLMError, MessageType, Messages};
 
 #[derive(Clone, Serialize, Default, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -87,7 +89,7 @@ pub struct ChatChoice {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-struct OpenAIResponse {
+pub struct OpenAIResponse {
     pub id: Option<String>,
     pub choices: Vec<ChatChoice>,
     pub created: u32,
@@ -95,7 +97,7 @@ struct OpenAIResponse {
     pub service_tier: Option<String>,
     pub system_fingerprint: Option<String>,
     pub object: String,
-    pub usage: Option<String>,
+    pub usage: Option<CompletionUsage>,
 }
 
 #[derive(Clone, Serialize, Debug, Deserialize, PartialEq)]
@@ -213,6 +215,51 @@ pub struct Property {
     pub enum_values: Option<Vec<String>>,
 }
 
+/// A message in the `messages` of an OpenAI compatible chat completion request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenAIContent {
+    pub role: String,
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+pub trait OpenAIMessages {
+    fn to_openai_messages(&self) -> Vec<OpenAIContent>;
+    fn to_json_value(&self) -> Value;
+}
+
+impl OpenAIMessages for Messages {
+    fn to_openai_messages(&self) -> Vec<OpenAIContent> {
+        let mut contents: Vec<OpenAIContent> = Vec::new();
+        for message in self.messages.iter() {
+            let role = match message.message_type {
+                MessageType::AIMessage => "assistant",
+                MessageType::HumanMessage => "user",
+                MessageType::SystemMessage => "system",
+                MessageType::ToolMessage => "tool",
+            }
+            .to_string();
+            let tool_calls = message.tool_calls.clone();
+            let gemini_message = OpenAIContent {
+                content: message.content.clone(),
+                role,
+                tool_calls,
+                tool_call_id: message.id.clone(),
+            };
+            contents.push(gemini_message);
+        }
+        contents
+    }
+
+    fn to_json_value(&self) -> Value {
+        let contents: Vec<OpenAIContent> = self.to_openai_messages();
+        serde_json::to_value(contents).unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -1,3 +1,5 @@
+use std::ops::AddAssign;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -23,6 +25,13 @@ impl TokenUsage {
     }
 }
 
+/// Lets a state accumulate usage with the `Sum` reducer.
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: TokenUsage) {
+        self.add(&other);
+    }
+}
+
 impl TokenUsage {
     pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
         Self {
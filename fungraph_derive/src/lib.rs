@@ -1,5 +1,5 @@
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::*;
 
 #[proc_macro_derive(ToolParameters)]
@@ -7,7 +7,7 @@ pub fn tool_parameters_derive(input: TokenStream) -> TokenStream {
     let ast = syn::parse(input).unwrap();
 
     match impl_tool_parameters(&ast) {
-        Ok(expanded) => expanded.into(),
+        Ok(expanded) => expanded,
         Err(e) => e.to_compile_error().into(),
     }
 }
@@ -19,12 +19,12 @@ fn impl_tool_parameters(ast: &DeriveInput) -> Result<TokenStream> {
         Data::Struct(data) => match &data.fields {
             Fields::Named(fields) => &fields.named,
             _ => Err(syn::Error::new_spanned(
-                &ast,
+                ast,
                 "ToolParameters derive only supports named fields",
             ))?,
         },
         _ => Err(syn::Error::new_spanned(
-            &ast,
+            ast,
             "ToolParameters derive only supports structs",
         ))?,
     };
@@ -103,6 +103,181 @@ fn impl_tool_parameters(ast: &DeriveInput) -> Result<TokenStream> {
     Ok(gen_code.into())
 }
 
+#[proc_macro_derive(FunState, attributes(reducer, serde))]
+pub fn fun_state_derive(input: TokenStream) -> TokenStream {
+    let ast = syn::parse(input).unwrap();
+
+    match impl_fun_state(&ast) {
+        Ok(expanded) => expanded,
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn impl_fun_state(ast: &DeriveInput) -> Result<TokenStream> {
+    let name = &ast.ident;
+    let vis = &ast.vis;
+    let update_name = format_ident!("{}Update", name);
+
+    if !ast.generics.params.is_empty() {
+        Err(syn::Error::new_spanned(
+            &ast.generics,
+            "FunState derive does not support generic structs",
+        ))?
+    }
+
+    let fields = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => Err(syn::Error::new_spanned(
+                ast,
+                "FunState derive only supports named fields",
+            ))?,
+        },
+        _ => Err(syn::Error::new_spanned(
+            ast,
+            "FunState derive only supports structs",
+        ))?,
+    };
+
+    let idents = fields
+        .iter()
+        .map(|field| field.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let field_vis = fields.iter().map(|field| &field.vis).collect::<Vec<_>>();
+    let reducers = fields.iter().map(get_reducer).collect::<Result<Vec<_>>>()?;
+    let serde_attrs = fields
+        .iter()
+        .map(|field| get_serde_attributes(&field.attrs))
+        .collect::<Vec<_>>();
+    let container_serde_attrs = get_serde_attributes(&ast.attrs);
+    let setters = idents
+        .iter()
+        .map(|ident| format_ident!("with_{}", ident))
+        .collect::<Vec<_>>();
+    let update_doc = format!("Partial update of [`{}`] returned by nodes.", name);
+
+    let gen_code = quote! {
+        #[doc = #update_doc]
+        #[derive(Default, fungraph::serde::Serialize, fungraph::serde::Deserialize)]
+        #[serde(crate = "fungraph::serde")]
+        #vis struct #update_name {
+            #(
+                #[serde(default, skip_serializing_if = "Option::is_none")]
+                #field_vis #idents: Option<#types>,
+            )*
+        }
+
+        impl #update_name {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            #(
+                pub fn #setters(mut self, value: #types) -> Self {
+                    self.#idents = Some(value);
+                    self
+                }
+            )*
+        }
+
+        impl fungraph::node::FunState for #name {
+            type Update = #update_name;
+
+            fn apply(&mut self, update: Self::Update) {
+                #(
+                    <#reducers as fungraph::node::Reducer<#types>>::reduce_option(
+                        &mut self.#idents,
+                        update.#idents,
+                    );
+                )*
+            }
+        }
+
+        const _: () = {
+            #[derive(fungraph::serde::Serialize)]
+            #[serde(crate = "fungraph::serde")]
+            #(#container_serde_attrs)*
+            struct FunStateRef<'a> {
+                #(
+                    #(#serde_attrs)*
+                    #idents: &'a #types,
+                )*
+            }
+
+            #[derive(fungraph::serde::Deserialize)]
+            #[serde(crate = "fungraph::serde")]
+            #(#container_serde_attrs)*
+            struct FunStateOwned {
+                #(
+                    #(#serde_attrs)*
+                    #idents: #types,
+                )*
+            }
+
+            impl fungraph::serde::Serialize for #name {
+                fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+                where
+                    S: fungraph::serde::Serializer,
+                {
+                    fungraph::serde::Serialize::serialize(
+                        &FunStateRef {
+                            #(#idents: &self.#idents,)*
+                        },
+                        serializer,
+                    )
+                }
+            }
+
+            impl<'de> fungraph::serde::Deserialize<'de> for #name {
+                fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: fungraph::serde::Deserializer<'de>,
+                {
+                    let owned: FunStateOwned =
+                        fungraph::serde::Deserialize::deserialize(deserializer)?;
+                    Ok(Self {
+                        #(#idents: owned.#idents,)*
+                    })
+                }
+            }
+        };
+    };
+
+    Ok(gen_code.into())
+}
+
+/// `#[reducer(append)]`, `#[reducer(sum)]` and `#[reducer(last_write_wins)]` map to the built-in
+/// reducers. Any other path is used as a type implementing `fungraph::node::Reducer`.
+fn get_reducer(field: &Field) -> Result<proc_macro2::TokenStream> {
+    let attr = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("reducer"));
+
+    let Some(attr) = attr else {
+        return Ok(quote! { fungraph::node::LastWriteWins });
+    };
+
+    let path: Path = attr.parse_args()?;
+    if path.is_ident("append") {
+        Ok(quote! { fungraph::node::Append })
+    } else if path.is_ident("sum") {
+        Ok(quote! { fungraph::node::Sum })
+    } else if path.is_ident("last_write_wins") {
+        Ok(quote! { fungraph::node::LastWriteWins })
+    } else {
+        Ok(quote! { #path })
+    }
+}
+
+fn get_serde_attributes(attrs: &[Attribute]) -> Vec<&Attribute> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+        .collect()
+}
+
 fn get_data_type(field: &Field) -> Result<String> {
     let ty = &field.ty;
     let js_type = get_data_type_inner(ty)?;
@@ -120,7 +295,7 @@ fn get_data_type(field: &Field) -> Result<String> {
 fn get_data_type_inner(ty: &Type) -> Result<String> {
     match ty {
         Type::Path(type_path) => {
-            if type_path.path.segments.len() > 0 {
+            if !type_path.path.segments.is_empty() {
                 let ident = &type_path.path.segments[0].ident;
                 match ident.to_string().as_str() {
                     "String" => Ok("string".to_string()),
@@ -145,13 +320,12 @@ fn get_data_type_inner(ty: &Type) -> Result<String> {
 
 fn get_option_type(type_path: &TypePath) -> Result<String> {
     // Option 型のジェネリック引数を取得
-    if let PathArguments::AngleBracketed(args) = &type_path.path.segments[0].arguments {
-        if args.args.len() == 1 {
-            if let GenericArgument::Type(inner_type) = &args.args[0] {
-                // Option 型の内部の型に対して再帰的に get_data_type を呼び出す
-                return get_data_type_inner(inner_type);
-            }
-        }
+    if let PathArguments::AngleBracketed(args) = &type_path.path.segments[0].arguments
+        && args.args.len() == 1
+        && let GenericArgument::Type(inner_type) = &args.args[0]
+    {
+        // Option 型の内部の型に対して再帰的に get_data_type を呼び出す
+        return get_data_type_inner(inner_type);
     };
     Err(syn::Error::new_spanned(
         type_path,
@@ -164,14 +338,12 @@ fn get_description(field: &Field) -> Option<String> {
         .attrs
         .iter()
         .filter_map(|attr: &Attribute| {
-            if let Meta::NameValue(name_value) = &attr.meta {
-                if name_value.path.is_ident("doc") {
-                    if let syn::Expr::Lit(expr_lit) = &name_value.value {
-                        if let syn::Lit::Str(lit_str) = &expr_lit.lit {
-                            return Some(lit_str.value().trim().to_string());
-                        }
-                    }
-                }
+            if let Meta::NameValue(name_value) = &attr.meta
+                && name_value.path.is_ident("doc")
+                && let syn::Expr::Lit(expr_lit) = &name_value.value
+                && let syn::Lit::Str(lit_str) = &expr_lit.lit
+            {
+                return Some(lit_str.value().trim().to_string());
             }
             None
         })
@@ -256,6 +428,44 @@ mod tests {
         assert_eq!(description, None);
     }
 
+    #[test]
+    fn test_get_reducer_default() {
+        let field = parse_field(quote! {
+            pub field_name: String,
+        });
+
+        let reducer = get_reducer(&field).unwrap();
+        assert_eq!(
+            reducer.to_string(),
+            quote! { fungraph::node::LastWriteWins }.to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_reducer_builtin() {
+        let field = parse_field(quote! {
+            #[reducer(append)]
+            pub field_name: Vec<String>,
+        });
+
+        let reducer = get_reducer(&field).unwrap();
+        assert_eq!(
+            reducer.to_string(),
+            quote! { fungraph::node::Append }.to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_reducer_custom_path() {
+        let field = parse_field(quote! {
+            #[reducer(my_reducers::Max)]
+            pub field_name: i32,
+        });
+
+        let reducer = get_reducer(&field).unwrap();
+        assert_eq!(reducer.to_string(), quote! { my_reducers::Max }.to_string());
+    }
+
     #[test]
     fn test_get_description_other_attribute() {
         let field = parse_field(quote! {
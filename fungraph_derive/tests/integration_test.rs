@@ -1,5 +1,6 @@
-use fungraph::{tools::ToolParameters, types::openai::Parameters};
+use fungraph::{node::FunState, tools::ToolParameters, types::openai::Parameters};
 
+#[allow(dead_code)]
 #[derive(ToolParameters)]
 struct MyTool {
     /// This is a test description.
@@ -7,6 +8,7 @@ struct MyTool {
     age: i32,
 }
 
+#[allow(dead_code)]
 #[derive(ToolParameters)]
 struct MyOptionTool {
     /// This is a test description.
@@ -23,7 +25,7 @@ struct MyOptionTool {
 
 #[test]
 fn test_generated_parameters() {
-    let my_tool = MyTool {
+    let _my_tool = MyTool {
         name: "test".to_string(),
         age: 30,
     };
@@ -61,7 +63,7 @@ fn test_generated_parameters() {
 
 #[test]
 fn test_option_parameter() {
-    let my_tool = MyOptionTool {
+    let _my_tool = MyOptionTool {
         name: Some("test".to_string()),
         age: Some(30),
     };
@@ -88,3 +90,72 @@ fn test_option_parameter() {
     assert_eq!(age_property.r#type, "number".to_string());
     assert_eq!(age_property.description, None);
 }
+
+#[derive(Debug, Clone, Default, PartialEq, FunState)]
+struct ChatState {
+    #[reducer(append)]
+    messages: Vec<String>,
+    #[reducer(sum)]
+    turns: u32,
+    answer: Option<String>,
+    #[serde(rename = "lang")]
+    language: String,
+}
+
+#[test]
+fn test_fun_state_apply_update() {
+    let mut state = ChatState {
+        messages: vec!["hello".to_string()],
+        turns: 1,
+        answer: None,
+        language: "ja".to_string(),
+    };
+
+    let update = ChatStateUpdate::new()
+        .with_messages(vec!["world".to_string()])
+        .with_turns(2)
+        .with_answer(Some("done".to_string()));
+    state.apply(update);
+
+    // reducer(append) は追加、reducer(sum) は加算、指定なしは上書き
+    assert_eq!(state.messages, vec!["hello", "world"]);
+    assert_eq!(state.turns, 3);
+    assert_eq!(state.answer, Some("done".to_string()));
+    // 更新されなかったフィールドはそのまま
+    assert_eq!(state.language, "ja");
+}
+
+#[test]
+fn test_fun_state_serde() {
+    let state = ChatState {
+        messages: vec!["hello".to_string()],
+        turns: 1,
+        answer: Some("done".to_string()),
+        language: "ja".to_string(),
+    };
+
+    let value = serde_json::to_value(&state).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "messages": ["hello"],
+            "turns": 1,
+            "answer": "done",
+            "lang": "ja"
+        })
+    );
+
+    let restored: ChatState = serde_json::from_value(value).unwrap();
+    assert_eq!(restored, state);
+}
+
+#[test]
+fn test_fun_state_update_serde() {
+    let update = ChatStateUpdate::new().with_turns(2);
+    let value = serde_json::to_value(&update).unwrap();
+    assert_eq!(value, serde_json::json!({ "turns": 2 }));
+
+    let restored: ChatStateUpdate = serde_json::from_value(value).unwrap();
+    assert_eq!(restored.turns, Some(2));
+    assert!(restored.messages.is_none());
+}